@@ -0,0 +1,95 @@
+//! Compares erasing futures into stack futures against boxing them.
+use std::{
+    hint::black_box,
+    pin::{Pin, pin},
+    task::{Context, Poll, Waker},
+};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use stack_future::{SmallFuture, StackFuture};
+
+/// Yields once, so that the futures below are polled more than once.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if std::mem::replace(&mut self.0, true) {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+async fn small() -> u64 {
+    YieldOnce(false).await;
+    black_box(42)
+}
+
+async fn medium() -> u64 {
+    let buffer = black_box([1u8; 256]);
+    YieldOnce(false).await;
+    buffer.iter().map(|&x| x as u64).sum()
+}
+
+fn run<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn bench_small(c: &mut Criterion) {
+    let mut group = c.benchmark_group("small");
+    group.bench_function("StackFuture", |b| {
+        b.iter(|| run(StackFuture::<_, 64>::new(small()).unwrap()))
+    });
+    group.bench_function("SmallFuture inline", |b| {
+        b.iter(|| run(SmallFuture::<_, 64>::new(small())))
+    });
+    group.bench_function("SmallFuture heap", |b| {
+        b.iter(|| run(SmallFuture::<_, 64>::new_heap(small())))
+    });
+    group.bench_function("Box::pin", |b| {
+        b.iter(|| run(Box::pin(small()) as Pin<Box<dyn Future<Output = u64> + Send>>))
+    });
+    group.finish();
+}
+
+fn bench_medium(c: &mut Criterion) {
+    let mut group = c.benchmark_group("medium");
+    group.bench_function("StackFuture", |b| {
+        b.iter(|| run(StackFuture::<_, 512>::new(medium()).unwrap()))
+    });
+    group.bench_function("SmallFuture inline", |b| {
+        b.iter(|| run(SmallFuture::<_, 512>::new(medium())))
+    });
+    group.bench_function("SmallFuture heap", |b| {
+        b.iter(|| run(SmallFuture::<_, 512>::new_heap(medium())))
+    });
+    group.bench_function("Box::pin", |b| {
+        b.iter(|| run(Box::pin(medium()) as Pin<Box<dyn Future<Output = u64> + Send>>))
+    });
+    group.finish();
+}
+
+/// Creating a stack future should not touch the unused part of the buffer.
+fn bench_large_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large buffer");
+    group.bench_function("StackFuture 64", |b| {
+        b.iter(|| black_box(StackFuture::<_, 64>::new(small()).unwrap()))
+    });
+    group.bench_function("StackFuture 16384", |b| {
+        b.iter(|| black_box(StackFuture::<_, 16384>::new(small()).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_small, bench_medium, bench_large_buffer);
+criterion_main!(benches);
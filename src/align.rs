@@ -0,0 +1,59 @@
+//! Selection of the inline buffer alignment from a const generic.
+//!
+//! `repr(align)` can not take a const generic parameter, so each supported
+//! alignment maps to a zero-sized type with the matching `repr(align)` via the
+//! sealed [`SupportedAlignment`] trait.
+//!
+//! An unsupported alignment fails the `Align<ALIGN>: SupportedAlignment` bound
+//! at compile time. For example, a stack future for cache-line-aligned state is
+//! `StackFuture<'_, T, 256, 64>`.
+//!
+//! To support another alignment, add it to the `supported_alignments!` list
+//! below. It must be a power of two that `repr(align)` accepts.
+
+/// Marker type that maps a const alignment to an aligned zero-sized type.
+pub struct Align<const A: usize>;
+
+/// Implemented for [`Align<A>`] for every supported buffer alignment.
+///
+/// The supported alignments are the powers of two from 1 to 4096.
+pub trait SupportedAlignment: sealed::Sealed {
+    #[doc(hidden)]
+    type Aligned: Send + Sync;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! supported_alignments {
+    ($($align:literal => $name:ident),* $(,)?) => {
+        $(
+            #[doc(hidden)]
+            #[repr(align($align))]
+            pub struct $name;
+
+            impl sealed::Sealed for Align<$align> {}
+
+            impl SupportedAlignment for Align<$align> {
+                type Aligned = $name;
+            }
+        )*
+    };
+}
+
+supported_alignments! {
+    1 => Aligned1,
+    2 => Aligned2,
+    4 => Aligned4,
+    8 => Aligned8,
+    16 => Aligned16,
+    32 => Aligned32,
+    64 => Aligned64,
+    128 => Aligned128,
+    256 => Aligned256,
+    512 => Aligned512,
+    1024 => Aligned1024,
+    2048 => Aligned2048,
+    4096 => Aligned4096,
+}
@@ -0,0 +1,588 @@
+//! Combinators that erase an adapted stack future into a new stack future.
+//!
+//! The adapters store the original stack future together with their own state,
+//! so the buffer of the resulting stack future must be large enough for both.
+//! Since the original stack future is moved into the new buffer, this can only
+//! be done before it is pinned.
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+#[cfg(feature = "std")]
+use std::{
+    any::Any,
+    boxed::Box,
+    panic::{self, AssertUnwindSafe},
+};
+
+#[cfg(feature = "tokio")]
+use tokio::time::{Duration, error::Elapsed};
+
+use crate::{Align, CreateError, LocalStackFuture, StackFuture, SupportedAlignment};
+
+impl<'a, T: 'a, const N: usize, const ALIGN: usize> LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new stack future that calls `on_cancel` if it is dropped
+    /// before completing.
+    ///
+    /// `on_cancel` runs before the inner future is dropped, and not at all once
+    /// the future returned its output. The buffer must hold both the future and
+    /// the closure.
+    pub fn new_with_cancel<F: Future<Output = T> + 'a, C: FnOnce() + 'a>(
+        future: F,
+        on_cancel: C,
+    ) -> Result<Self, CreateError> {
+        LocalStackFuture::new(OnCancel {
+            future,
+            on_cancel: Some(on_cancel),
+        })
+    }
+
+    /// Maps the output of this future with a closure, without allocating.
+    ///
+    /// The resulting buffer of size `M` must hold both this stack future and
+    /// the closure.
+    pub fn map<U, G: FnOnce(T) -> U + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<LocalStackFuture<'a, U, M, ALIGN>, CreateError> {
+        LocalStackFuture::new(Map::new(self, f))
+    }
+
+    /// Runs a closure on the output of this future, and awaits the future it
+    /// returns, without allocating.
+    ///
+    /// The second future replaces this stack future in place once it completes,
+    /// so the resulting buffer of size `K` must hold the larger of this stack
+    /// future with the closure, and the second future.
+    pub fn then<Fut: Future + 'a, G: FnOnce(T) -> Fut + 'a, const K: usize>(
+        self,
+        f: G,
+    ) -> Result<LocalStackFuture<'a, Fut::Output, K, ALIGN>, CreateError> {
+        LocalStackFuture::new(Then::First {
+            future: self,
+            f: Some(f),
+        })
+    }
+
+    /// Calls a closure with a reference to the output of this future, and
+    /// passes the output on unchanged, without allocating.
+    ///
+    /// The closure runs exactly once, when the future completes. The resulting
+    /// buffer of size `M` must hold both this stack future and the closure.
+    pub fn inspect<G: FnOnce(&T) + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<LocalStackFuture<'a, T, M, ALIGN>, CreateError> {
+        LocalStackFuture::new(Map::new(self, move |output: T| {
+            f(&output);
+            output
+        }))
+    }
+
+    /// Requires this future to complete within `duration`, without allocating.
+    ///
+    /// The timer starts when this method is called. The resulting buffer of
+    /// size `M` must hold both this stack future and a [`tokio::time::Sleep`].
+    #[cfg(feature = "tokio")]
+    pub fn with_timeout<const M: usize>(
+        self,
+        duration: Duration,
+    ) -> Result<LocalStackFuture<'a, Result<T, Elapsed>, M, ALIGN>, CreateError> {
+        LocalStackFuture::new(tokio::time::timeout(duration, self))
+    }
+
+    /// Catches panics of this future, without allocating.
+    ///
+    /// A panic in `poll` completes the resulting future with the panic payload
+    /// as an error, and the inner future is not polled again. The resulting
+    /// buffer of size `M` must hold this stack future and a flag.
+    #[cfg(feature = "std")]
+    #[allow(clippy::type_complexity)]
+    pub fn catch_unwind<const M: usize>(
+        self,
+    ) -> Result<LocalStackFuture<'a, Result<T, Box<dyn Any + Send>>, M, ALIGN>, CreateError> {
+        LocalStackFuture::new(CatchUnwind::new(self))
+    }
+}
+
+impl<'a, T: 'a, const N: usize, const ALIGN: usize> StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new stack future that calls `on_cancel` if it is dropped
+    /// before completing.
+    ///
+    /// `on_cancel` runs before the inner future is dropped, and not at all once
+    /// the future returned its output. The buffer must hold both the future and
+    /// the closure.
+    pub fn new_with_cancel<F: Future<Output = T> + Send + 'a, C: FnOnce() + Send + 'a>(
+        future: F,
+        on_cancel: C,
+    ) -> Result<Self, CreateError> {
+        StackFuture::new(OnCancel {
+            future,
+            on_cancel: Some(on_cancel),
+        })
+    }
+
+    /// Maps the output of this future with a closure, without allocating.
+    ///
+    /// The resulting buffer of size `M` must hold both this stack future and
+    /// the closure.
+    pub fn map<U, G: FnOnce(T) -> U + Send + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<StackFuture<'a, U, M, ALIGN>, CreateError> {
+        StackFuture::new(Map::new(self, f))
+    }
+
+    /// Runs a closure on the output of this future, and awaits the future it
+    /// returns, without allocating.
+    ///
+    /// The second future replaces this stack future in place once it completes,
+    /// so the resulting buffer of size `K` must hold the larger of this stack
+    /// future with the closure, and the second future.
+    pub fn then<Fut: Future + Send + 'a, G: FnOnce(T) -> Fut + Send + 'a, const K: usize>(
+        self,
+        f: G,
+    ) -> Result<StackFuture<'a, Fut::Output, K, ALIGN>, CreateError> {
+        StackFuture::new(Then::First {
+            future: self,
+            f: Some(f),
+        })
+    }
+
+    /// Calls a closure with a reference to the output of this future, and
+    /// passes the output on unchanged, without allocating.
+    ///
+    /// The closure runs exactly once, when the future completes. The resulting
+    /// buffer of size `M` must hold both this stack future and the closure.
+    pub fn inspect<G: FnOnce(&T) + Send + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<StackFuture<'a, T, M, ALIGN>, CreateError> {
+        StackFuture::new(Map::new(self, move |output: T| {
+            f(&output);
+            output
+        }))
+    }
+
+    /// Requires this future to complete within `duration`, without allocating.
+    ///
+    /// The timer starts when this method is called. The resulting buffer of
+    /// size `M` must hold both this stack future and a [`tokio::time::Sleep`].
+    #[cfg(feature = "tokio")]
+    pub fn with_timeout<const M: usize>(
+        self,
+        duration: Duration,
+    ) -> Result<StackFuture<'a, Result<T, Elapsed>, M, ALIGN>, CreateError> {
+        StackFuture::new(tokio::time::timeout(duration, self))
+    }
+
+    /// Catches panics of this future, without allocating.
+    ///
+    /// A panic in `poll` completes the resulting future with the panic payload
+    /// as an error, and the inner future is not polled again. The resulting
+    /// buffer of size `M` must hold this stack future and a flag.
+    #[cfg(feature = "std")]
+    #[allow(clippy::type_complexity)]
+    pub fn catch_unwind<const M: usize>(
+        self,
+    ) -> Result<StackFuture<'a, Result<T, Box<dyn Any + Send>>, M, ALIGN>, CreateError> {
+        StackFuture::new(CatchUnwind::new(self))
+    }
+}
+
+impl<'a, V: 'a, E: 'a, const N: usize, const ALIGN: usize>
+    LocalStackFuture<'a, Result<V, E>, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Maps the error of this future with a closure, without allocating.
+    ///
+    /// The resulting buffer of size `M` must hold both this stack future and
+    /// the closure.
+    pub fn map_err<E2, G: FnOnce(E) -> E2 + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<LocalStackFuture<'a, Result<V, E2>, M, ALIGN>, CreateError> {
+        LocalStackFuture::new(Map::new(self, move |res: Result<V, E>| res.map_err(f)))
+    }
+}
+
+impl<'a, V: 'a, E: 'a, const N: usize, const ALIGN: usize> StackFuture<'a, Result<V, E>, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Maps the error of this future with a closure, without allocating.
+    ///
+    /// The resulting buffer of size `M` must hold both this stack future and
+    /// the closure.
+    pub fn map_err<E2, G: FnOnce(E) -> E2 + Send + 'a, const M: usize>(
+        self,
+        f: G,
+    ) -> Result<StackFuture<'a, Result<V, E2>, M, ALIGN>, CreateError> {
+        StackFuture::new(Map::new(self, move |res: Result<V, E>| res.map_err(f)))
+    }
+}
+
+impl<'a, T: Future + 'a, const N: usize, const ALIGN: usize> LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Awaits the future produced by this future, without allocating.
+    ///
+    /// The resulting buffer of size `K` must hold the larger of this stack
+    /// future and the produced future.
+    pub fn flatten<const K: usize>(
+        self,
+    ) -> Result<LocalStackFuture<'a, T::Output, K, ALIGN>, CreateError> {
+        LocalStackFuture::new(Flatten::First(self))
+    }
+}
+
+impl<'a, T: Future + Send + 'a, const N: usize, const ALIGN: usize> StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Awaits the future produced by this future, without allocating.
+    ///
+    /// The resulting buffer of size `K` must hold the larger of this stack
+    /// future and the produced future.
+    pub fn flatten<const K: usize>(
+        self,
+    ) -> Result<StackFuture<'a, T::Output, K, ALIGN>, CreateError> {
+        StackFuture::new(Flatten::First(self))
+    }
+}
+
+/// Races two stack futures, without allocating.
+///
+/// Both futures are polled on every wakeup. The output of the first one to
+/// complete is returned, and the other one is dropped. The resulting buffer of
+/// size `M` must hold both stack futures.
+pub fn select2<'a, T: 'a, const N: usize, const M: usize, const ALIGN: usize>(
+    a: StackFuture<'a, T, N, ALIGN>,
+    b: StackFuture<'a, T, N, ALIGN>,
+) -> Result<StackFuture<'a, Either<T, T>, M, ALIGN>, CreateError>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    StackFuture::new(Select::new(a, b))
+}
+
+/// Races two local stack futures, without allocating.
+///
+/// This is the non-Send version of [`select2`].
+pub fn select2_local<'a, T: 'a, const N: usize, const M: usize, const ALIGN: usize>(
+    a: LocalStackFuture<'a, T, N, ALIGN>,
+    b: LocalStackFuture<'a, T, N, ALIGN>,
+) -> Result<LocalStackFuture<'a, Either<T, T>, M, ALIGN>, CreateError>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    LocalStackFuture::new(Select::new(a, b))
+}
+
+/// Awaits two stack futures to completion, without allocating.
+///
+/// Both futures are polled until each has completed, and a completed future is
+/// dropped right away and not polled again. The resulting buffer of size `M`
+/// must hold both stack futures and both outputs.
+pub fn join2<'a, A: Send + 'a, B: Send + 'a, const N: usize, const M: usize, const ALIGN: usize>(
+    a: StackFuture<'a, A, N, ALIGN>,
+    b: StackFuture<'a, B, N, ALIGN>,
+) -> Result<StackFuture<'a, (A, B), M, ALIGN>, CreateError>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    StackFuture::new(Join::new(a, b))
+}
+
+/// Awaits two local stack futures to completion, without allocating.
+///
+/// This is the non-Send version of [`join2`].
+pub fn join2_local<'a, A: 'a, B: 'a, const N: usize, const M: usize, const ALIGN: usize>(
+    a: LocalStackFuture<'a, A, N, ALIGN>,
+    b: LocalStackFuture<'a, B, N, ALIGN>,
+) -> Result<LocalStackFuture<'a, (A, B), M, ALIGN>, CreateError>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    LocalStackFuture::new(Join::new(a, b))
+}
+
+/// A value of one of two types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Polls whichever future is stored, if both have the same output.
+impl<L, R> Future for Either<L, R>
+where
+    L: Future,
+    R: Future<Output = L::Output>,
+{
+    type Output = L::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the stored future is structurally pinned and never moved out.
+        unsafe {
+            match self.get_unchecked_mut() {
+                Either::Left(l) => Pin::new_unchecked(l).poll(cx),
+                Either::Right(r) => Pin::new_unchecked(r).poll(cx),
+            }
+        }
+    }
+}
+
+/// Future for [`select2`].
+struct Select<A, B> {
+    a: Option<A>,
+    b: Option<B>,
+}
+
+impl<A, B> Select<A, B> {
+    fn new(a: A, b: B) -> Self {
+        Self {
+            a: Some(a),
+            b: Some(b),
+        }
+    }
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because both futures are structurally pinned and only dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut a = unsafe { Pin::new_unchecked(&mut this.a) };
+        let mut b = unsafe { Pin::new_unchecked(&mut this.b) };
+        let output = match (a.as_mut().as_pin_mut(), b.as_mut().as_pin_mut()) {
+            (Some(fa), Some(fb)) => {
+                if let Poll::Ready(x) = fa.poll(cx) {
+                    Either::Left(x)
+                } else if let Poll::Ready(x) = fb.poll(cx) {
+                    Either::Right(x)
+                } else {
+                    return Poll::Pending;
+                }
+            }
+            _ => panic!("Select polled after completion"),
+        };
+        a.set(None);
+        b.set(None);
+        Poll::Ready(output)
+    }
+}
+
+/// Future for [`join2`].
+struct Join<A: Future, B: Future> {
+    a: Option<A>,
+    b: Option<B>,
+    a_out: Option<A::Output>,
+    b_out: Option<B::Output>,
+}
+
+impl<A: Future, B: Future> Join<A, B> {
+    fn new(a: A, b: B) -> Self {
+        Self {
+            a: Some(a),
+            b: Some(b),
+            a_out: None,
+            b_out: None,
+        }
+    }
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because both futures are structurally pinned and only dropped in
+        // place, and the outputs are never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut a = unsafe { Pin::new_unchecked(&mut this.a) };
+        if let Some(fa) = a.as_mut().as_pin_mut()
+            && let Poll::Ready(x) = fa.poll(cx)
+        {
+            this.a_out = Some(x);
+            a.set(None);
+        }
+        let mut b = unsafe { Pin::new_unchecked(&mut this.b) };
+        if let Some(fb) = b.as_mut().as_pin_mut()
+            && let Poll::Ready(x) = fb.poll(cx)
+        {
+            this.b_out = Some(x);
+            b.set(None);
+        }
+        if this.a.is_some() || this.b.is_some() {
+            return Poll::Pending;
+        }
+        match (this.a_out.take(), this.b_out.take()) {
+            (Some(a), Some(b)) => Poll::Ready((a, b)),
+            _ => panic!("Join polled after completion"),
+        }
+    }
+}
+
+/// Future for [`StackFuture::flatten`].
+enum Flatten<A, B> {
+    First(A),
+    Second(B),
+    Done,
+}
+
+impl<A: Future<Output = B>, B: Future> Future for Flatten<A, B> {
+    type Output = B::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            // Safe because the stored future is structurally pinned, and is only
+            // dropped in place by `set`.
+            match unsafe { self.as_mut().get_unchecked_mut() } {
+                Flatten::First(a) => {
+                    let b = ready!(unsafe { Pin::new_unchecked(a) }.poll(cx));
+                    self.set(Flatten::Second(b));
+                }
+                Flatten::Second(b) => {
+                    let output = ready!(unsafe { Pin::new_unchecked(b) }.poll(cx));
+                    self.set(Flatten::Done);
+                    return Poll::Ready(output);
+                }
+                Flatten::Done => panic!("Flatten polled after completion"),
+            }
+        }
+    }
+}
+
+/// Future for [`StackFuture::then`].
+enum Then<A, G, B> {
+    First { future: A, f: Option<G> },
+    Second(B),
+    Done,
+}
+
+impl<A: Future, G: FnOnce(A::Output) -> B, B: Future> Future for Then<A, G, B> {
+    type Output = B::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            // Safe because the stored futures are structurally pinned and only
+            // dropped in place by `set`, and the closure is never pinned.
+            match unsafe { self.as_mut().get_unchecked_mut() } {
+                Then::First { future, f } => {
+                    let output = ready!(unsafe { Pin::new_unchecked(future) }.poll(cx));
+                    let f = f.take().expect("Then polled after panic");
+                    // The first future is dropped in place before the closure
+                    // runs, and the second one is written over it.
+                    self.set(Then::Done);
+                    self.set(Then::Second(f(output)));
+                }
+                Then::Second(b) => {
+                    let output = ready!(unsafe { Pin::new_unchecked(b) }.poll(cx));
+                    self.set(Then::Done);
+                    return Poll::Ready(output);
+                }
+                Then::Done => panic!("Then polled after completion"),
+            }
+        }
+    }
+}
+
+/// Future for [`StackFuture::map`].
+struct Map<Fut, G> {
+    future: Fut,
+    f: Option<G>,
+}
+
+impl<Fut, G> Map<Fut, G> {
+    fn new(future: Fut, f: G) -> Self {
+        Self { future, f: Some(f) }
+    }
+}
+
+impl<Fut: Future, G: FnOnce(Fut::Output) -> U, U> Future for Map<Fut, G> {
+    type Output = U;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the future is structurally pinned, and the closure is never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let output = ready!(future.poll(cx));
+        let f = this.f.take().expect("Map polled after completion");
+        Poll::Ready(f(output))
+    }
+}
+
+/// Future for [`StackFuture::new_with_cancel`].
+struct OnCancel<F, C: FnOnce()> {
+    future: F,
+    // Taken on completion, so it only runs on cancellation.
+    on_cancel: Option<C>,
+}
+
+impl<F: Future, C: FnOnce()> Future for OnCancel<F, C> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the future is structurally pinned, and the closure is never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let output = ready!(future.poll(cx));
+        this.on_cancel = None;
+        Poll::Ready(output)
+    }
+}
+
+impl<F, C: FnOnce()> Drop for OnCancel<F, C> {
+    fn drop(&mut self) {
+        if let Some(on_cancel) = self.on_cancel.take() {
+            on_cancel();
+        }
+    }
+}
+
+/// Future for [`StackFuture::catch_unwind`].
+#[cfg(feature = "std")]
+struct CatchUnwind<Fut> {
+    future: Fut,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<Fut> CatchUnwind<Fut> {
+    fn new(future: Fut) -> Self {
+        Self {
+            future,
+            done: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Fut: Future> Future for CatchUnwind<Fut> {
+    type Output = Result<Fut::Output, Box<dyn Any + Send>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the future is structurally pinned, and the flag is never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        assert!(!this.done, "CatchUnwind polled after completion");
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let res = match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => return Poll::Pending,
+            Ok(Poll::Ready(output)) => Ok(output),
+            Err(payload) => Err(payload),
+        };
+        this.done = true;
+        Poll::Ready(res)
+    }
+}
@@ -0,0 +1,66 @@
+//! Conversions from concrete futures, as an extension trait.
+//!
+//! Blanket `From<F>` and `TryFrom<F>` impls for the wrappers would overlap with
+//! the reflexive impls in `core`, since the wrappers are futures themselves, so
+//! the conversions are provided as methods instead.
+use crate::{Align, CreateError, LocalStackFuture, StackFuture, SupportedAlignment};
+#[cfg(feature = "alloc")]
+use crate::{LocalSmallFuture, SmallFuture};
+
+/// Extension methods for erasing a concrete future.
+pub trait StackFutureExt: Future + Sized {
+    /// Erases the future into a [`StackFuture`].
+    ///
+    /// This is the fallible counterpart of [`into_small`](Self::into_small),
+    /// and forwards to [`StackFuture::new`].
+    ///
+    /// ```
+    /// use stack_future::{CreateError, StackFuture, StackFutureExt};
+    ///
+    /// let future: StackFuture<_, 16> = core::future::ready(42u64).try_into_stack().unwrap();
+    /// let res = core::future::ready(42u64).try_into_stack::<8, 8>();
+    /// assert!(matches!(res, Err(CreateError::SizeTooLarge { size: 16, max_size: 8, .. })));
+    /// ```
+    fn try_into_stack<'a, const N: usize, const ALIGN: usize>(
+        self,
+    ) -> Result<StackFuture<'a, Self::Output, N, ALIGN>, CreateError>
+    where
+        Self: Send + 'a,
+        Align<ALIGN>: SupportedAlignment,
+    {
+        StackFuture::new(self)
+    }
+
+    /// Erases the future into a [`LocalStackFuture`].
+    fn try_into_local_stack<'a, const N: usize, const ALIGN: usize>(
+        self,
+    ) -> Result<LocalStackFuture<'a, Self::Output, N, ALIGN>, CreateError>
+    where
+        Self: 'a,
+        Align<ALIGN>: SupportedAlignment,
+    {
+        LocalStackFuture::new(self)
+    }
+
+    /// Erases the future into a [`SmallFuture`], falling back to the heap if it
+    /// does not fit.
+    #[cfg(feature = "alloc")]
+    fn into_small<'a, const N: usize>(self) -> SmallFuture<'a, Self::Output, N>
+    where
+        Self: Send + 'a,
+    {
+        SmallFuture::new(self)
+    }
+
+    /// Erases the future into a [`LocalSmallFuture`], falling back to the heap
+    /// if it does not fit.
+    #[cfg(feature = "alloc")]
+    fn into_local_small<'a, const N: usize>(self) -> LocalSmallFuture<'a, Self::Output, N>
+    where
+        Self: 'a,
+    {
+        LocalSmallFuture::new(self)
+    }
+}
+
+impl<F: Future> StackFutureExt for F {}
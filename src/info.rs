@@ -0,0 +1,28 @@
+//! A description of the layout of an erased future, for logging and metrics.
+
+/// Where an erased future is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Storage {
+    /// In the inline buffer.
+    Inline,
+    /// On the heap, because it did not fit inline.
+    Heap,
+}
+
+/// The buffer layout of an erased future, as returned by `info`.
+///
+/// With the `serde` feature, this implements `Serialize`, so it can be logged
+/// to a structured sink. The future itself can not be serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StackFutureInfo {
+    /// The size of the inline buffer in bytes.
+    pub capacity: usize,
+    /// The size of the erased future in bytes.
+    pub stored_size: usize,
+    /// The alignment of the erased future in bytes.
+    pub stored_align: usize,
+    /// Where the erased future is stored.
+    pub storage: Storage,
+}
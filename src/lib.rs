@@ -1,38 +1,335 @@
-mod stack_future;
-use std::{
+#![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+#![cfg_attr(feature = "async_iterator", feature(async_iterator))]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::{
+    any::{TypeId, type_name},
+    marker::PhantomData,
+    mem::{self, MaybeUninit, align_of, size_of},
     pin::Pin,
     ptr,
     task::{Context, Poll},
 };
 
-pub use stack_future::{CreateError, LocalStackFuture, StackFuture};
+#[cfg(feature = "futures")]
+use futures_core::FusedFuture;
 
+mod align;
+#[cfg(feature = "std")]
+mod block_on;
+mod combinators;
+mod ext;
+mod info;
+#[cfg(feature = "alloc")]
+mod pool;
+#[cfg(feature = "alloc")]
 mod small_future;
+#[cfg(feature = "alloc")]
+mod stack_box;
+mod stack_future;
+#[cfg(feature = "futures-sink")]
+mod stack_sink;
+#[cfg(feature = "futures")]
+mod stack_stream;
+mod try_future;
+mod unpin_stack_future;
+
+pub use align::{Align, SupportedAlignment};
+#[cfg(feature = "std")]
+pub use block_on::block_on;
+pub use combinators::{Either, join2, join2_local, select2, select2_local};
+pub use ext::StackFutureExt;
+pub use info::{StackFutureInfo, Storage};
+#[cfg(feature = "alloc")]
+pub use pool::StackFuturePool;
+#[cfg(feature = "alloc")]
 pub use small_future::{LocalSmallFuture, SmallFuture};
-/// A wrapper to enforce coarse alignment on the buffer.
+#[cfg(feature = "alloc")]
+pub use stack_box::StackBox;
+pub use stack_future::{
+    CreateError, LocalStackFuture, Overflow, RawVTable, SendRawVTable, StackFuture,
+    StaticStackFuture, poll_all,
+};
+#[cfg(feature = "futures-sink")]
+pub use stack_sink::{LocalStackSink, StackSink};
+#[cfg(feature = "futures")]
+pub use stack_stream::{LocalStackStream, StackStream};
+pub use try_future::{LocalStackTryFuture, StackTryFuture};
+pub use unpin_stack_future::{LocalUnpinStackFuture, UnpinStackFuture};
+
+/// Returns the size of a future type, i.e. the buffer size it needs.
+pub const fn future_size<F>() -> usize {
+    size_of::<F>()
+}
+
+/// Returns true if a future type fits into a buffer of size `N` with the
+/// default alignment, i.e. if [`StackFuture::new`] would succeed for it.
+pub const fn fits<F, const N: usize>() -> bool {
+    size_of::<F>() <= N && align_of::<F>() <= align_of::<AlignedBuffer<N>>()
+}
+
+/// Returns the size of the future produced by an expression, without running it.
+///
+/// The expression is only type checked, which makes this work for the unnameable
+/// futures returned by `async fn`. Values it uses are still moved.
+///
+/// ```
+/// use stack_future::future_size;
+///
+/// async fn answer() -> u64 {
+///     42
+/// }
+///
+/// assert!(future_size!(answer()) <= 16);
+/// ```
+#[macro_export]
+macro_rules! future_size {
+    ($future:expr) => {{
+        const fn size<F>(_: &::core::option::Option<F>) -> usize {
+            ::core::mem::size_of::<F>()
+        }
+        let mut future = ::core::option::Option::None;
+        #[allow(unreachable_code)]
+        if false {
+            future = ::core::option::Option::Some($future);
+        }
+        size(&future)
+    }};
+}
+
+/// Asserts at compile time that a future type fits into a buffer of the given size.
+///
+/// This checks both the size and the alignment of the type, so that
+/// [`StackFuture::new`] is guaranteed to succeed for it. An optional third
+/// argument gives the buffer alignment, which defaults to 16.
+///
+/// ```
+/// use stack_future::stack_future_assert_fits;
+///
+/// stack_future_assert_fits!(core::future::Ready<u64>, 16);
+/// stack_future_assert_fits!(core::future::Ready<u128>, 32, 16);
+/// ```
+///
+/// Futures returned by `async fn` can not be named, so they can not be checked
+/// with this macro.
+#[macro_export]
+macro_rules! stack_future_assert_fits {
+    ($ty:ty, $n:expr) => {
+        $crate::stack_future_assert_fits!($ty, $n, 16);
+    };
+    ($ty:ty, $n:expr, $align:expr) => {
+        const _: () = {
+            assert!(
+                ::core::mem::size_of::<$ty>() <= $n,
+                "future does not fit into the buffer"
+            );
+            assert!(
+                ::core::mem::align_of::<$ty>()
+                    <= $crate::StackFuture::<(), { $n }, { $align }>::alignment(),
+                "future alignment exceeds buffer alignment"
+            );
+        };
+    };
+}
+
+/// Erases a future into the smallest of several stack future sizes that fits.
 ///
-/// todo: is 8 bytes alignment enough?
-#[repr(align(8))]
-struct AlignedBuffer<const N: usize> {
-    // todo: use MaybeUninit to avoid zero-initialization
-    buffer: [u8; N],
+/// The sizes are tried in order, and the future is boxed if it does not fit
+/// into any of them. The result is a nested [`Either`], with one [`StackFuture`]
+/// per size and the boxed future on the right. It implements [`Future`], so it
+/// can be awaited directly.
+///
+/// ```
+/// use stack_future::{Either, stack_future};
+///
+/// let future = stack_future![async { 42 }; 32, 64, 128];
+/// assert!(matches!(future, Either::Left(_)));
+/// assert_eq!(futures::executor::block_on(future), 42);
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! stack_future {
+    ($future:expr; $n:expr $(,)?) => {
+        $crate::StackFuture::<_, { $n }>::new_or_box($future)
+    };
+    ($future:expr; $n:expr, $($rest:expr),+ $(,)?) => {
+        match $crate::StackFuture::<_, { $n }>::try_new($future) {
+            ::core::result::Result::Ok(future) => $crate::Either::Left(future),
+            ::core::result::Result::Err((_, future)) => {
+                $crate::Either::Right($crate::stack_future!(future; $($rest),+))
+            }
+        }
+    };
+}
+
+/// A wrapper to enforce alignment on the buffer.
+///
+/// The alignment defaults to 16 bytes and can be raised via the `ALIGN` parameter.
+struct AlignedBuffer<const N: usize, const ALIGN: usize = 16>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    _align: [<Align<ALIGN> as SupportedAlignment>::Aligned; 0],
+    // Left uninitialized; only the region written by `ptr::write` is ever read back.
+    buffer: [MaybeUninit<u8>; N],
+}
+
+impl<const N: usize, const ALIGN: usize> AlignedBuffer<N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new, uninitialized buffer.
+    const fn uninit() -> Self {
+        Self {
+            _align: [],
+            buffer: [MaybeUninit::uninit(); N],
+        }
+    }
+
+    /// Checks if a value of type `F` fits in the buffer and has compatible alignment.
+    fn check<F>() -> Result<(), CreateError> {
+        if size_of::<F>() > N {
+            return Err(CreateError::SizeTooLarge {
+                size: size_of::<F>(),
+                max_size: N,
+                type_name: type_name::<F>(),
+            });
+        }
+
+        if align_of::<F>() > align_of::<Self>() {
+            return Err(CreateError::AlignmentMismatch {
+                alignment: align_of::<F>(),
+                expected: align_of::<Self>(),
+                type_name: type_name::<F>(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 struct VTable<T> {
     poll: unsafe fn(*mut u8, cx: &mut Context<'_>) -> Poll<T>,
     drop: unsafe fn(*mut u8),
+    #[cfg(feature = "futures")]
+    is_terminated: unsafe fn(*const u8) -> bool,
+    // Clones the erased future into uninitialized memory, if it is cloneable.
+    clone: Option<unsafe fn(*const u8, *mut u8)>,
+    // Size and alignment of the erased future.
+    size: usize,
+    align: usize,
+    // Type id of the erased future with lifetimes erased, and its name for errors.
+    // These share one function pointer to keep the vtable small.
+    type_info: fn() -> (TypeId, &'static str),
 }
 
+// Stack futures hold a reference to their vtable, which is promoted to a
+// constant per future type, so that the vtable does not add to the size of
+// every stack future.
 impl<T> VTable<T> {
-    fn new<'a, F: Future<Output = T> + 'a>() -> &'a Self {
-        &Self {
-            poll: |ptr, cx| {
-                let future = unsafe { &mut *(ptr as *mut F) };
-                unsafe { Pin::new_unchecked(future).poll(cx) }
-            },
-            drop: |ptr| {
-                unsafe { ptr::drop_in_place(ptr as *mut F) };
-            },
+    const fn new<'a, F: Future<Output = T> + 'a>() -> &'a Self
+    where
+        T: 'a,
+    {
+        const { &Self::create::<F>() }
+    }
+
+    /// Like [`VTable::new`], but forwards `is_terminated` to the inner future.
+    #[cfg(feature = "futures")]
+    const fn new_fused<'a, F: FusedFuture<Output = T> + 'a>() -> &'a Self
+    where
+        T: 'a,
+    {
+        const {
+            &Self {
+                is_terminated: |ptr| {
+                    let future = unsafe { &*(ptr as *const F) };
+                    future.is_terminated()
+                },
+                ..Self::create::<F>()
+            }
+        }
+    }
+
+    /// Like [`VTable::new`], but can clone the inner future.
+    const fn new_cloneable<'a, F: Future<Output = T> + Clone + 'a>() -> &'a Self
+    where
+        T: 'a,
+    {
+        const {
+            &Self {
+                clone: Some(|src, dst| unsafe {
+                    ptr::write(dst as *mut F, (*(src as *const F)).clone());
+                }),
+                ..Self::create::<F>()
+            }
+        }
+    }
+
+    const fn create<F: Future<Output = T>>() -> Self {
+        Self {
+            poll: poll_shim::<F>,
+            drop: drop_shim::<F>,
+            #[cfg(feature = "futures")]
+            is_terminated: |_| false,
+            clone: None,
+            size: size_of::<F>(),
+            align: align_of::<F>(),
+            type_info: type_info::<F>,
         }
     }
 }
+
+// The vtable shims are named functions rather than closures, so that they are
+// identifiable in backtraces. They are `#[inline]` so that the inner `poll` can
+// be inlined into them, leaving a single crate frame above user code.
+
+/// Polls the erased future of type `F` at `ptr`.
+#[inline]
+unsafe fn poll_shim<F: Future>(ptr: *mut u8, cx: &mut Context<'_>) -> Poll<F::Output> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut F)) }.poll(cx)
+}
+
+/// Drops the erased future of type `F` at `ptr` in place.
+#[inline]
+unsafe fn drop_shim<F>(ptr: *mut u8) {
+    unsafe { ptr::drop_in_place(ptr as *mut F) };
+}
+
+/// Returns the erased [`TypeId`] and the name of `F`, for the vtable.
+fn type_info<F>() -> (TypeId, &'static str) {
+    (erased_type_id::<F>(), type_name::<F>())
+}
+
+/// Returns the [`TypeId`] of `F` with all lifetimes erased.
+///
+/// `Foo<'a>` and `Foo<'static>` have the same id, so a match does not prove
+/// that the lifetimes agree.
+fn erased_type_id<F>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<F> NonStaticAny for PhantomData<F> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<F>()
+        }
+    }
+
+    let phantom = PhantomData::<F>;
+    let erased: &dyn NonStaticAny = &phantom;
+    // Safe because only the trait object lifetime is extended, and `type_id`
+    // neither stores nor dereferences anything of type `F`.
+    let erased =
+        unsafe { mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(erased) };
+    erased.type_id()
+}
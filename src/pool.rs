@@ -0,0 +1,114 @@
+//! A fixed set of stack futures that are polled together.
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Align, StackFuture, SupportedAlignment};
+
+/// A pool of stack futures in one contiguous allocation, polled round-robin.
+///
+/// This is a minimal `FuturesUnordered` for stack futures of the same type. The
+/// slots are allocated once, and a future stays in its slot until it completes
+/// or the pool is dropped, so the futures are pinned without being boxed
+/// individually. Completed slots are reused by later pushes.
+///
+/// The capacity is fixed: growing the slots like a `Vec` would move the pinned
+/// futures.
+pub struct StackFuturePool<'a, T, const N: usize = 64, const ALIGN: usize = 16>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    // Never resized, so that the futures are never moved.
+    slots: Box<[Option<StackFuture<'a, T, N, ALIGN>>]>,
+    len: usize,
+    // Slot to start polling at, so that an always ready future can not starve
+    // the ones after it.
+    next: usize,
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> StackFuturePool<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates an empty pool with room for `capacity` futures.
+    pub fn new(capacity: usize) -> Self {
+        let mut slots = Vec::with_capacity(capacity);
+        slots.resize_with(capacity, || None);
+        Self {
+            slots: slots.into_boxed_slice(),
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Adds a future to a free slot and returns the slot index.
+    ///
+    /// If the pool is full, the future is handed back.
+    pub fn push(
+        &mut self,
+        future: StackFuture<'a, T, N, ALIGN>,
+    ) -> Result<usize, StackFuture<'a, T, N, ALIGN>> {
+        let Some(index) = self.slots.iter().position(Option::is_none) else {
+            return Err(future);
+        };
+        self.slots[index] = Some(future);
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// Returns the number of pending futures in the pool.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the pool holds no futures.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum number of futures the pool can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Polls all futures in the pool, and returns the output of the first one to
+    /// complete together with its slot index.
+    ///
+    /// Polling starts after the slot that completed last, so the slots take
+    /// turns. The completed future is dropped and its slot is freed. If the pool
+    /// is empty, this returns `Poll::Pending`.
+    pub fn poll_any(&mut self, cx: &mut Context<'_>) -> Poll<(usize, T)> {
+        let capacity = self.slots.len();
+        for index in (self.next..capacity).chain(0..self.next) {
+            let slot = &mut self.slots[index];
+            let Some(future) = slot else {
+                continue;
+            };
+            // Safe because the slots are never moved, and the future is only
+            // dropped in place.
+            let future = unsafe { Pin::new_unchecked(future) };
+            if let Poll::Ready(output) = future.poll(cx) {
+                *slot = None;
+                self.len -= 1;
+                self.next = (index + 1) % capacity;
+                return Poll::Ready((index, output));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> fmt::Debug for StackFuturePool<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackFuturePool")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
@@ -1,66 +1,41 @@
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
 use core::{
     fmt,
     future::Future,
-    mem::{align_of, size_of},
+    marker::PhantomPinned,
+    mem,
     pin::Pin,
     ptr,
     task::{Context, Poll},
 };
-use std::{
-    alloc::{Layout, alloc, dealloc},
-    marker::{PhantomData, PhantomPinned},
-    rc::Rc,
-};
-
-use crate::{AlignedBuffer, VTable};
-
-// A wrapper for heap-allocated buffer with dynamic alignment.
-struct HeapBuffer {
-    ptr: *mut u8,
-    layout: Layout,
-}
 
-impl HeapBuffer {
-    fn new<F>() -> Self {
-        let size = size_of::<F>();
-        let align = align_of::<F>();
-        let layout = Layout::from_size_align(size, align).unwrap();
-        let ptr = unsafe { alloc(layout) };
-        if ptr.is_null() {
-            panic!("Heap allocation failed");
-        }
-        unsafe {
-            ptr::write_bytes(ptr, 0, size);
-        }
-        Self { ptr, layout }
-    }
-
-    fn as_mut_ptr(&mut self) -> *mut u8 {
-        self.ptr
-    }
-}
+use crate::{
+    CreateError, LocalStackFuture, RawVTable, StackBox, StackFuture, StackFutureInfo, Storage,
+};
 
-impl Drop for HeapBuffer {
-    fn drop(&mut self) {
-        unsafe {
-            dealloc(self.ptr, self.layout);
-        }
-    }
-}
+/// Polls the future stored in a [`StackBox`].
+type PollFn<T> = unsafe fn(*mut u8, &mut Context<'_>) -> Poll<T>;
 
-unsafe impl Send for HeapBuffer {}
-unsafe impl Sync for HeapBuffer {}
+/// Returns the full vtable of the future stored in a [`StackBox`], for
+/// converting it into a stack future.
+type VTableFn<T> = fn() -> RawVTable<T>;
 
 /// A stack-allocated future that erases the concrete type, falling back to heap if needed.
 ///
 /// This is non-Send and !Unpin, safe for any future (e.g., containing Rc).
-/// Use `SmallFutureSend` for Send futures in multi-threaded contexts.
+/// Use `SmallFuture` for Send futures in multi-threaded contexts.
 /// Note: Due to !Unpin, this may require boxing (e.g., `Box::pin`) for Unpin-requiring APIs.
-#[repr(transparent)]
-pub struct LocalSmallFuture<'a, T, const N: usize>(
-    State<'a, T, N>,
+///
+/// A future that was stored inline can be turned into a [`LocalStackFuture`] with
+/// [`into_stack_if_inline`](Self::into_stack_if_inline).
+#[must_use = "futures do nothing unless awaited"]
+pub struct LocalSmallFuture<'a, T, const N: usize = 64>(
+    StackBox<'a, N>,
+    PollFn<T>,
+    VTableFn<T>,
+    u64,
     PhantomPinned,
-    PhantomData<Rc<()>>,
 );
 
 impl<'a, T, const N: usize> LocalSmallFuture<'a, T, N> {
@@ -68,23 +43,149 @@ impl<'a, T, const N: usize> LocalSmallFuture<'a, T, N> {
     ///
     /// Uses stack allocation if the future fits and has compatible alignment; otherwise, falls back to heap.
     pub fn new<F: Future<Output = T> + 'a>(future: F) -> Self {
-        Self(State::new(future), PhantomPinned, PhantomData)
+        Self(
+            StackBox::new(future),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// Creates a new small future, handing the future back if it does not fit
+    /// inline and the heap allocation fails.
+    pub fn try_new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, (CreateError, F)> {
+        Ok(Self(
+            StackBox::try_new(future)?,
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        ))
+    }
+
+    /// Creates a new small future inline, returning an error instead of
+    /// falling back to the heap if it does not fit.
+    pub fn try_new_inline<F: Future<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
+        Ok(Self(
+            StackBox::try_new_inline(future)?,
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        ))
+    }
+
+    /// Creates a new small future on the heap, even if it would fit inline.
+    ///
+    /// This is mostly useful for testing code paths that handle heap-backed futures.
+    pub fn new_heap<F: Future<Output = T> + 'a>(future: F) -> Self {
+        Self(
+            StackBox::new_heap(future),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// Creates a new small future, using `alloc` if the future does not fit inline.
+    ///
+    /// The allocator is stored in the heap allocation, so the type does not
+    /// depend on it. If the future fits inline, the allocator is dropped unused.
+    #[cfg(feature = "allocator_api")]
+    pub fn new_in<F: Future<Output = T> + 'a, A: Allocator + 'a>(future: F, alloc: A) -> Self {
+        Self(
+            StackBox::new_in(future, alloc),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        StackBox::<N>::alignment()
+    }
+
+    /// Returns true if the future is stored in the inline buffer.
+    pub fn is_inline(&self) -> bool {
+        self.0.is_inline()
+    }
+
+    /// Returns true if the future did not fit inline and was moved to the heap.
+    pub fn is_heap(&self) -> bool {
+        self.0.is_heap()
+    }
+
+    /// Returns the size of the stored future, whether it is inline or on the heap.
+    pub fn stored_size(&self) -> usize {
+        self.0.stored_size()
+    }
+
+    /// Returns the alignment of the stored future, whether it is inline or on the heap.
+    pub fn stored_align(&self) -> usize {
+        self.0.stored_align()
+    }
+
+    /// Returns the buffer layout of this small future, e.g. for logging.
+    pub fn info(&self) -> StackFutureInfo {
+        StackFutureInfo {
+            capacity: N,
+            stored_size: self.0.stored_size(),
+            stored_align: self.0.stored_align(),
+            storage: if self.0.is_inline() {
+                Storage::Inline
+            } else {
+                Storage::Heap
+            },
+        }
+    }
+
+    /// Returns how often the future has been polled.
+    pub fn poll_count(&self) -> u64 {
+        self.3
+    }
+
+    /// Converts this small future into a [`LocalStackFuture`] if it is stored inline,
+    /// and hands it back otherwise.
+    ///
+    /// The inline buffer is moved as is. This is sound because a small future
+    /// can only be moved, and so converted, before it is pinned and polled.
+    pub fn into_stack_if_inline(self) -> Result<LocalStackFuture<'a, T, N>, Self> {
+        let this = mem::ManuallyDrop::new(self);
+        // Safe because `this` is never dropped, so the storage is moved out once.
+        let (storage, poll, vtable, polls) =
+            (unsafe { ptr::read(&this.0) }, this.1, this.2, this.3);
+        match storage.into_inline() {
+            // Safe because the buffer holds the future that `vtable` was created
+            // for, which lives for `'a`.
+            Ok(buffer) => Ok(unsafe { LocalStackFuture::from_raw(buffer, vtable()) }),
+            Err(storage) => Err(Self(storage, poll, vtable, polls, PhantomPinned)),
+        }
     }
 }
 
 impl<'a, T, const N: usize> fmt::Debug for LocalSmallFuture<'a, T, N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.0 {
-            State::Inline { .. } => f
-                .debug_struct("SmallFuture")
-                .field("storage", &"Inline")
-                .finish(),
-            State::Heap { buffer, .. } => f
-                .debug_struct("SmallFuture")
-                .field("storage", &"Heap")
-                .field("size", &buffer.layout.size())
-                .field("align", &buffer.layout.align())
-                .finish(),
+        self.0.fmt_debug("LocalSmallFuture", f)
+    }
+}
+
+#[cfg(feature = "detect_unpolled")]
+impl<'a, T, const N: usize> Drop for LocalSmallFuture<'a, T, N> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) && self.3 == 0 {
+            std::eprintln!("warning: small future dropped without being polled");
         }
     }
 }
@@ -94,23 +195,8 @@ impl<'a, T, const N: usize> Future for LocalSmallFuture<'a, T, N> {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         unsafe {
             let this = self.get_unchecked_mut();
-            match &mut this.0 {
-                State::Inline { buffer, vtable } => (vtable.poll)(buffer.buffer.as_mut_ptr(), cx),
-                State::Heap { buffer, vtable } => (vtable.poll)(buffer.as_mut_ptr(), cx),
-            }
-        }
-    }
-}
-
-impl<'a, T, const N: usize> Drop for LocalSmallFuture<'a, T, N> {
-    fn drop(&mut self) {
-        match &mut self.0 {
-            State::Inline { buffer, vtable } => unsafe {
-                (vtable.drop)(buffer.buffer.as_mut_ptr());
-            },
-            State::Heap { buffer, vtable } => unsafe {
-                (vtable.drop)(buffer.as_mut_ptr());
-            },
+            this.3 += 1;
+            (this.1)(this.0.as_mut_ptr(), cx)
         }
     }
 }
@@ -119,31 +205,179 @@ impl<'a, T, const N: usize> Drop for LocalSmallFuture<'a, T, N> {
 ///
 /// This is Send, Sync, and !Unpin, suitable for Send futures in multi-threaded contexts (e.g., tokio::spawn).
 /// Note: Due to !Unpin, this may require boxing (e.g., `Box::pin`) for Unpin-requiring APIs.
-#[repr(transparent)]
-pub struct SmallFuture<'a, T, const N: usize>(State<'a, T, N>, PhantomPinned);
+///
+/// A future that was stored inline can be turned into a [`StackFuture`] with
+/// [`into_stack_if_inline`](Self::into_stack_if_inline).
+#[must_use = "futures do nothing unless awaited"]
+pub struct SmallFuture<'a, T, const N: usize = 64>(
+    StackBox<'a, N>,
+    PollFn<T>,
+    VTableFn<T>,
+    u64,
+    PhantomPinned,
+);
+
+// Safety: the constructors require the future and the allocator to be Send.
+// `LocalSmallFuture` gets neither impl, so its storage can not make it Send.
+unsafe impl<'a, T, const N: usize> Send for SmallFuture<'a, T, N> {}
+
+// Safety: the future is only accessed through `Pin<&mut Self>`, so a shared
+// reference gives no access to it, even if the future itself is not Sync.
+unsafe impl<'a, T, const N: usize> Sync for SmallFuture<'a, T, N> {}
 
 impl<'a, T, const N: usize> SmallFuture<'a, T, N> {
     /// Creates a new stack future from a concrete Send future.
     ///
     /// Uses stack allocation if the future fits and has compatible alignment; otherwise, falls back to heap.
     pub fn new<F: Future<Output = T> + Send + 'a>(future: F) -> Self {
-        Self(State::new(future), PhantomPinned)
+        Self(
+            StackBox::new(future),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// Creates a new small future, handing the future back if it does not fit
+    /// inline and the heap allocation fails.
+    pub fn try_new<F: Future<Output = T> + Send + 'a>(future: F) -> Result<Self, (CreateError, F)> {
+        Ok(Self(
+            StackBox::try_new(future)?,
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        ))
+    }
+
+    /// Creates a new small future inline, returning an error instead of
+    /// falling back to the heap if it does not fit.
+    pub fn try_new_inline<F: Future<Output = T> + Send + 'a>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Ok(Self(
+            StackBox::try_new_inline(future)?,
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        ))
+    }
+
+    /// Creates a new small future on the heap, even if it would fit inline.
+    ///
+    /// This is mostly useful for testing code paths that handle heap-backed futures.
+    pub fn new_heap<F: Future<Output = T> + Send + 'a>(future: F) -> Self {
+        Self(
+            StackBox::new_heap(future),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// Creates a new small future, using `alloc` if the future does not fit inline.
+    ///
+    /// The allocator is stored in the heap allocation, so the type does not
+    /// depend on it. If the future fits inline, the allocator is dropped unused.
+    #[cfg(feature = "allocator_api")]
+    pub fn new_in<F: Future<Output = T> + Send + 'a, A: Allocator + Send + 'a>(
+        future: F,
+        alloc: A,
+    ) -> Self {
+        Self(
+            StackBox::new_in(future, alloc),
+            poll_fn::<F>(),
+            RawVTable::of::<F>,
+            0,
+            PhantomPinned,
+        )
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        StackBox::<N>::alignment()
+    }
+
+    /// Returns true if the future is stored in the inline buffer.
+    pub fn is_inline(&self) -> bool {
+        self.0.is_inline()
+    }
+
+    /// Returns true if the future did not fit inline and was moved to the heap.
+    pub fn is_heap(&self) -> bool {
+        self.0.is_heap()
+    }
+
+    /// Returns the size of the stored future, whether it is inline or on the heap.
+    pub fn stored_size(&self) -> usize {
+        self.0.stored_size()
+    }
+
+    /// Returns the alignment of the stored future, whether it is inline or on the heap.
+    pub fn stored_align(&self) -> usize {
+        self.0.stored_align()
+    }
+
+    /// Returns the buffer layout of this small future, e.g. for logging.
+    pub fn info(&self) -> StackFutureInfo {
+        StackFutureInfo {
+            capacity: N,
+            stored_size: self.0.stored_size(),
+            stored_align: self.0.stored_align(),
+            storage: if self.0.is_inline() {
+                Storage::Inline
+            } else {
+                Storage::Heap
+            },
+        }
+    }
+
+    /// Returns how often the future has been polled.
+    pub fn poll_count(&self) -> u64 {
+        self.3
+    }
+
+    /// Converts this small future into a [`StackFuture`] if it is stored inline,
+    /// and hands it back otherwise.
+    ///
+    /// The inline buffer is moved as is. This is sound because a small future
+    /// can only be moved, and so converted, before it is pinned and polled.
+    pub fn into_stack_if_inline(self) -> Result<StackFuture<'a, T, N>, Self> {
+        let this = mem::ManuallyDrop::new(self);
+        // Safe because `this` is never dropped, so the storage is moved out once.
+        let (storage, poll, vtable, polls) =
+            (unsafe { ptr::read(&this.0) }, this.1, this.2, this.3);
+        match storage.into_inline() {
+            // Safe because the buffer holds the future that `vtable` was created
+            // for, which lives for `'a`, and is Send.
+            Ok(buffer) => Ok(unsafe { StackFuture::from_raw(buffer, vtable()) }),
+            Err(storage) => Err(Self(storage, poll, vtable, polls, PhantomPinned)),
+        }
     }
 }
 
 impl<'a, T, const N: usize> fmt::Debug for SmallFuture<'a, T, N> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.0 {
-            State::Inline { .. } => f
-                .debug_struct("SmallFutureSend")
-                .field("storage", &"Inline")
-                .finish(),
-            State::Heap { buffer, .. } => f
-                .debug_struct("SmallFutureSend")
-                .field("storage", &"Heap")
-                .field("size", &buffer.layout.size())
-                .field("align", &buffer.layout.align())
-                .finish(),
+        self.0.fmt_debug("SmallFuture", f)
+    }
+}
+
+#[cfg(feature = "detect_unpolled")]
+impl<'a, T, const N: usize> Drop for SmallFuture<'a, T, N> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) && self.3 == 0 {
+            std::eprintln!("warning: small future dropped without being polled");
         }
     }
 }
@@ -153,54 +387,13 @@ impl<'a, T, const N: usize> Future for SmallFuture<'a, T, N> {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         unsafe {
             let this = self.get_unchecked_mut();
-            match &mut this.0 {
-                State::Inline { buffer, vtable } => (vtable.poll)(buffer.buffer.as_mut_ptr(), cx),
-                State::Heap { buffer, vtable } => (vtable.poll)(buffer.as_mut_ptr(), cx),
-            }
-        }
-    }
-}
-
-impl<'a, T, const N: usize> Drop for SmallFuture<'a, T, N> {
-    fn drop(&mut self) {
-        match &mut self.0 {
-            State::Inline { buffer, vtable } => unsafe {
-                (vtable.drop)(buffer.buffer.as_mut_ptr());
-            },
-            State::Heap { buffer, vtable } => unsafe {
-                (vtable.drop)(buffer.as_mut_ptr());
-            },
+            this.3 += 1;
+            (this.1)(this.0.as_mut_ptr(), cx)
         }
     }
 }
 
-enum State<'a, T, const N: usize> {
-    Inline {
-        buffer: AlignedBuffer<N>,
-        vtable: &'a VTable<T>,
-    },
-    Heap {
-        buffer: HeapBuffer,
-        vtable: &'a VTable<T>,
-    },
-}
-
-impl<'a, T: 'a, const N: usize> State<'a, T, N> {
-    fn new<F: Future<Output = T> + 'a>(future: F) -> Self {
-        if size_of::<F>() <= N && align_of::<F>() <= align_of::<AlignedBuffer<N>>() {
-            let vtable = VTable::new::<F>();
-            let mut buffer = AlignedBuffer { buffer: [0u8; N] };
-            unsafe {
-                ptr::write(buffer.buffer.as_mut_ptr() as *mut F, future);
-            }
-            Self::Inline { buffer, vtable }
-        } else {
-            let vtable = VTable::new::<F>();
-            let mut buffer = HeapBuffer::new::<F>();
-            unsafe {
-                ptr::write(buffer.as_mut_ptr() as *mut F, future);
-            }
-            Self::Heap { buffer, vtable }
-        }
-    }
+/// Returns the function that polls a future of type `F` stored in a [`StackBox`].
+fn poll_fn<F: Future>() -> PollFn<F::Output> {
+    |ptr, cx| unsafe { Pin::new_unchecked(&mut *ptr.cast::<F>()).poll(cx) }
 }
@@ -0,0 +1,296 @@
+//! An inline buffer for values of any type, falling back to the heap.
+//!
+//! This is the storage underneath [`SmallFuture`](crate::SmallFuture), exposed
+//! for erasing values that are not futures, e.g. closures.
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
+use alloc::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+use core::{
+    any::type_name,
+    fmt,
+    marker::PhantomData,
+    mem::{self, MaybeUninit, align_of, size_of},
+    ptr,
+};
+
+use crate::{AlignedBuffer, CreateError};
+
+// A wrapper for heap-allocated buffer with dynamic alignment.
+struct HeapBuffer {
+    ptr: *mut u8,
+    layout: Layout,
+    // Frees the allocation, given the pointer to the future and its layout.
+    release: unsafe fn(*mut u8, Layout),
+}
+
+impl HeapBuffer {
+    fn new<F>() -> Self {
+        match Self::try_new::<F>() {
+            Ok(buffer) => buffer,
+            Err(_) => handle_alloc_error(Layout::new::<F>()),
+        }
+    }
+
+    /// Like [`HeapBuffer::new`], but returns an error if the allocation fails.
+    fn try_new<F>() -> Result<Self, CreateError> {
+        let size = size_of::<F>();
+        let align = align_of::<F>();
+        let layout = Layout::from_size_align(size, align).unwrap();
+        if size == 0 {
+            // Allocating zero bytes is undefined behavior, and a zero-sized
+            // value only needs an aligned pointer.
+            return Ok(Self {
+                ptr: ptr::without_provenance_mut(align),
+                layout,
+                release: |_, _| {},
+            });
+        }
+        let ptr = unsafe { alloc(layout) };
+        if ptr.is_null() {
+            return Err(CreateError::AllocFailed {
+                size,
+                align,
+                type_name: type_name::<F>(),
+            });
+        }
+        Ok(Self {
+            ptr,
+            layout,
+            release: |ptr, layout| unsafe { dealloc(ptr, layout) },
+        })
+    }
+
+    /// Allocates space for a future of type `F` using a custom allocator.
+    ///
+    /// The allocator is moved into the allocation next to the future, so that
+    /// it is available for deallocation without being part of the type.
+    #[cfg(feature = "allocator_api")]
+    fn new_in<F, A: Allocator>(alloc: A) -> Self {
+        let layout = Layout::new::<HeapCell<A, F>>();
+        let cell = match alloc.allocate(layout) {
+            Ok(ptr) => ptr.as_ptr().cast::<HeapCell<A, F>>(),
+            Err(_) => handle_alloc_error(layout),
+        };
+        unsafe {
+            ptr::write(&raw mut (*cell).alloc, alloc);
+        }
+        Self {
+            ptr: unsafe { (&raw mut (*cell).future).cast() },
+            layout: Layout::new::<F>(),
+            release: |ptr, _| unsafe {
+                let cell = ptr
+                    .sub(core::mem::offset_of!(HeapCell<A, F>, future))
+                    .cast::<HeapCell<A, F>>();
+                let alloc = ptr::read(&raw const (*cell).alloc);
+                alloc.deallocate(
+                    core::ptr::NonNull::new_unchecked(cell.cast()),
+                    Layout::new::<HeapCell<A, F>>(),
+                );
+            },
+        }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Drop for HeapBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            (self.release)(self.ptr, self.layout);
+        }
+    }
+}
+
+/// The heap allocation made by [`HeapBuffer::new_in`].
+#[cfg(feature = "allocator_api")]
+#[repr(C)]
+struct HeapCell<A, F> {
+    alloc: A,
+    future: F,
+}
+
+/// A type-erased value, stored inline if it fits and on the heap otherwise.
+///
+/// The buffer holds `N` bytes with an alignment of 16. Only the destructor of
+/// the value is kept, so it can be accessed through [`as_ptr`](Self::as_ptr)
+/// and [`as_mut_ptr`](Self::as_mut_ptr) by code that knows its type.
+///
+/// This is neither Send nor Sync, since the value can be of any type.
+pub struct StackBox<'a, const N: usize = 64> {
+    storage: Storage<N>,
+    drop: unsafe fn(*mut u8),
+    // Size and alignment of the stored value.
+    size: usize,
+    align: usize,
+    _marker: PhantomData<(&'a (), *const ())>,
+}
+
+enum Storage<const N: usize> {
+    Inline(AlignedBuffer<N>),
+    Heap(HeapBuffer),
+}
+
+impl<'a, const N: usize> StackBox<'a, N> {
+    /// Stores `value` inline if it fits and has compatible alignment, and on the
+    /// heap otherwise.
+    pub fn new<V: 'a>(value: V) -> Self {
+        if Self::fits::<V>() {
+            Self::inline(value)
+        } else {
+            Self::heap(value, HeapBuffer::new::<V>())
+        }
+    }
+
+    /// Like [`new`](Self::new), but hands the value back if the heap allocation fails.
+    pub fn try_new<V: 'a>(value: V) -> Result<Self, (CreateError, V)> {
+        if Self::fits::<V>() {
+            return Ok(Self::inline(value));
+        }
+        match HeapBuffer::try_new::<V>() {
+            Ok(buffer) => Ok(Self::heap(value, buffer)),
+            Err(error) => Err((error, value)),
+        }
+    }
+
+    /// Stores `value` inline, returning an error instead of falling back to the heap.
+    pub fn try_new_inline<V: 'a>(value: V) -> Result<Self, CreateError> {
+        AlignedBuffer::<N>::check::<V>()?;
+        Ok(Self::inline(value))
+    }
+
+    /// Stores `value` on the heap, even if it would fit inline.
+    pub fn new_heap<V: 'a>(value: V) -> Self {
+        Self::heap(value, HeapBuffer::new::<V>())
+    }
+
+    /// Like [`new`](Self::new), but uses `alloc` if the value does not fit inline.
+    ///
+    /// The allocator is stored in the heap allocation, so the type does not
+    /// depend on it. If the value fits inline, the allocator is dropped unused.
+    #[cfg(feature = "allocator_api")]
+    pub fn new_in<V: 'a, A: Allocator + 'a>(value: V, alloc: A) -> Self {
+        if Self::fits::<V>() {
+            Self::inline(value)
+        } else {
+            Self::heap(value, HeapBuffer::new_in::<V, A>(alloc))
+        }
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        align_of::<AlignedBuffer<N>>()
+    }
+
+    /// Returns true if the value is stored in the inline buffer.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline(_))
+    }
+
+    /// Returns true if the value did not fit inline and was moved to the heap.
+    pub fn is_heap(&self) -> bool {
+        !self.is_inline()
+    }
+
+    /// Returns the size of the stored value, whether it is inline or on the heap.
+    pub fn stored_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the alignment of the stored value, whether it is inline or on the heap.
+    pub fn stored_align(&self) -> usize {
+        self.align
+    }
+
+    /// Returns a pointer to the stored value.
+    pub fn as_ptr(&self) -> *const u8 {
+        match &self.storage {
+            Storage::Inline(buffer) => buffer.buffer.as_ptr().cast(),
+            Storage::Heap(buffer) => buffer.ptr,
+        }
+    }
+
+    /// Returns a mutable pointer to the stored value.
+    ///
+    /// If the value is stored inline, moving the box moves the value.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        match &mut self.storage {
+            Storage::Inline(buffer) => buffer.buffer.as_mut_ptr().cast(),
+            Storage::Heap(buffer) => buffer.as_mut_ptr(),
+        }
+    }
+
+    /// Moves the inline buffer out without dropping the value, or hands the box
+    /// back if the value is on the heap.
+    pub(crate) fn into_inline(self) -> Result<[MaybeUninit<u8>; N], Self> {
+        if self.is_heap() {
+            return Err(self);
+        }
+        let this = mem::ManuallyDrop::new(self);
+        match &this.storage {
+            // Safe because `this` is never dropped, so the value is moved out once.
+            Storage::Inline(buffer) => Ok(unsafe { ptr::read(&buffer.buffer) }),
+            Storage::Heap(_) => unreachable!(),
+        }
+    }
+
+    /// Formats the storage and the stored value, for the wrappers' `Debug` impls.
+    pub(crate) fn fmt_debug(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name)
+            .field("storage", &if self.is_inline() { "Inline" } else { "Heap" })
+            .field("size", &self.size)
+            .field("align", &self.align)
+            .finish()
+    }
+
+    fn fits<V>() -> bool {
+        size_of::<V>() <= N && align_of::<V>() <= align_of::<AlignedBuffer<N>>()
+    }
+
+    fn inline<V: 'a>(value: V) -> Self {
+        let mut buffer = AlignedBuffer::uninit();
+        unsafe {
+            ptr::write(buffer.buffer.as_mut_ptr().cast::<V>(), value);
+        }
+        Self::from_storage::<V>(Storage::Inline(buffer))
+    }
+
+    fn heap<V: 'a>(value: V, mut buffer: HeapBuffer) -> Self {
+        unsafe {
+            ptr::write(buffer.as_mut_ptr().cast::<V>(), value);
+        }
+        Self::from_storage::<V>(Storage::Heap(buffer))
+    }
+
+    fn from_storage<V>(storage: Storage<N>) -> Self {
+        Self {
+            storage,
+            drop: |ptr| unsafe { ptr::drop_in_place(ptr.cast::<V>()) },
+            size: size_of::<V>(),
+            align: align_of::<V>(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, const N: usize> fmt::Debug for StackBox<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_debug("StackBox", f)
+    }
+}
+
+impl<'a, const N: usize> Drop for StackBox<'a, N> {
+    fn drop(&mut self) {
+        // The heap buffer, if any, is freed after the value is dropped.
+        unsafe { (self.drop)(self.as_mut_ptr()) };
+    }
+}
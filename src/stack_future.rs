@@ -8,189 +8,1840 @@
 //!
 //! Creating a [`StackFuture`] will fail if the future is too large or has
 //! too big alignment requirements.
+//!
+//! The buffer size defaults to 64 bytes, so `StackFuture<'a, T>` is the 64 byte
+//! variant. This fits small futures, but most `async fn` futures that hold
+//! state across an await need more. Every stack future takes up the full buffer
+//! size, so raising it for all futures wastes stack space.
+//!
+//! Zero-sized futures fit into any buffer, including one with `N = 0`. Note
+//! that `async` blocks are never zero-sized, since they store their state.
+//!
+//! The buffer alignment defaults to 16 bytes, which covers SIMD and 128-bit
+//! atomic types at the cost of a little padding. Futures with larger alignment
+//! requirements can be stored by raising it, e.g. `StackFuture<'_, T, 128, 32>`.
+//!
+//! All `N` bytes of the buffer are usable, whether or not `N` is a multiple of
+//! the alignment. A future's size is always a multiple of its own alignment, so
+//! no bytes are lost at the end of the buffer. The stack future itself is padded
+//! to a multiple of the alignment though, so picking `N` as such a multiple
+//! avoids paying for bytes that can never hold a future.
+//!
+//! # Pinning
+//!
+//! Stack futures are `!Unpin`, since the inner future is polled in place. They
+//! can be embedded in other futures and structurally pinned like any other
+//! `!Unpin` future, and `Pin<&mut StackFuture>` can be awaited directly.
+//!
+//! Polling only needs `core`: an executor can build a [`Context`] from its own
+//! [`Waker`](core::task::Waker), e.g. one made from a `RawWaker`, and call
+//! [`Future::poll`] on a pinned stack future. Once it has been polled, the stack
+//! future must not be moved until it is dropped, which `core::pin::pin!` or a
+//! statically allocated task slot guarantees.
+//!
+//! # Reentrancy
+//!
+//! A stack future holds no state besides the inner future while that future is
+//! being polled or dropped, and both require exclusive access. So an inner
+//! future may wake its own task from `poll` or from its destructor, e.g. via a
+//! stored [`Waker`](core::task::Waker). An executor that runs tasks from `wake`
+//! must not re-enter a task that is being dropped, as for any other future.
+//!
+//! # Panics
+//!
+//! If the inner future panics while being polled, the panic propagates to the
+//! caller and the stack future stays valid. Dropping it afterwards drops the
+//! inner future exactly once, just like dropping the concrete future would.
+//! Safe futures must remain droppable after a panic in `poll`, so this never
+//! causes undefined behavior.
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
 use core::{
-    future::Future,
-    mem::{align_of, size_of},
-    pin::Pin,
-    ptr,
-    task::{Context, Poll},
-};
-use std::{
+    any::type_name,
     fmt,
+    future::{Future, IntoFuture, Pending, pending, poll_fn, ready},
     marker::{PhantomData, PhantomPinned},
-    rc::Rc,
+    mem::{self, MaybeUninit, align_of, size_of},
+    pin::{Pin, pin},
+    ptr,
     result::Result,
+    task::{Context, Poll, Waker, ready},
 };
 
-use crate::{AlignedBuffer, VTable};
+#[cfg(feature = "futures")]
+use futures_core::FusedFuture;
+
+#[cfg(feature = "alloc")]
+use crate::Either;
+use crate::{
+    Align, AlignedBuffer, StackFutureInfo, Storage, SupportedAlignment, VTable, erased_type_id,
+};
 
+/// The reason a future could not be stored in a buffer.
+///
+/// `type_name` is the name of the offending future type, as given by
+/// [`core::any::type_name`]. It is meant for diagnostics only.
 #[derive(Debug)]
 pub enum CreateError {
-    SizeTooLarge { size: usize, max_size: usize },
-    AlignmentMismatch { alignment: usize, expected: usize },
+    SizeTooLarge {
+        size: usize,
+        max_size: usize,
+        type_name: &'static str,
+    },
+    AlignmentMismatch {
+        alignment: usize,
+        expected: usize,
+        type_name: &'static str,
+    },
+    /// The heap allocation for a future that did not fit inline failed.
+    AllocFailed {
+        size: usize,
+        align: usize,
+        type_name: &'static str,
+    },
 }
 
 impl fmt::Display for CreateError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CreateError::SizeTooLarge { size, max_size } => {
+            CreateError::SizeTooLarge {
+                size,
+                max_size,
+                type_name,
+            } => {
                 write!(
                     f,
-                    "Future size exceeds buffer capacity: {size} > {max_size}"
+                    "Future `{type_name}` size {size} exceeds capacity {max_size}"
                 )
             }
             CreateError::AlignmentMismatch {
                 alignment,
                 expected,
+                type_name,
+            } => {
+                write!(
+                    f,
+                    "Future `{type_name}` alignment {alignment} exceeds buffer alignment {expected}"
+                )
+            }
+            CreateError::AllocFailed {
+                size,
+                align,
+                type_name,
             } => {
                 write!(
                     f,
-                    "Future alignment exceeds buffer alignment: {alignment} > {expected}"
+                    "Failed to allocate {size} bytes with alignment {align} for future `{type_name}`"
                 )
             }
         }
     }
 }
 
-impl std::error::Error for CreateError {}
+impl core::error::Error for CreateError {}
 
-/// A stack-allocated future that erases the concrete type of the future.
+/// Maps [`CreateError::SizeTooLarge`] and [`CreateError::AllocFailed`] to
+/// [`ErrorKind::OutOfMemory`], and [`CreateError::AlignmentMismatch`] to
+/// [`ErrorKind::InvalidInput`].
 ///
-/// This is the non-Send version of the future.
-#[repr(transparent)]
-pub struct LocalStackFuture<'a, T, const N: usize>(StackFutureImpl<'a, T, N>, PhantomData<Rc<()>>);
+/// [`ErrorKind::OutOfMemory`]: std::io::ErrorKind::OutOfMemory
+/// [`ErrorKind::InvalidInput`]: std::io::ErrorKind::InvalidInput
+#[cfg(feature = "std")]
+impl From<CreateError> for std::io::Error {
+    fn from(error: CreateError) -> Self {
+        let kind = match error {
+            CreateError::SizeTooLarge { .. } | CreateError::AllocFailed { .. } => {
+                std::io::ErrorKind::OutOfMemory
+            }
+            CreateError::AlignmentMismatch { .. } => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+/// What to do when a future does not fit into the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Return the [`CreateError`].
+    Error,
+    /// Store the future on the heap, keeping only the box in the buffer.
+    ///
+    /// This requires the buffer to fit a pointer. Since the box itself is stored
+    /// inline, [`info`](StackFuture::info) reports [`Storage::Inline`](crate::Storage::Inline)
+    /// with the size of the box.
+    #[cfg(feature = "alloc")]
+    Box,
+    /// Panic with the [`CreateError`].
+    ///
+    /// This unwinds like any other panic, so it can be caught with `catch_unwind`.
+    Panic,
+}
+
+/// The vtable of a stack future that was split up with `into_raw`.
+///
+/// This is opaque, and only meant to be passed back to `from_raw`. It is
+/// neither Send nor Sync, since the future it describes may not be. Use
+/// [`into_send`](Self::into_send) to move the parts of a Send future to another
+/// thread.
+///
+/// ```compile_fail
+/// use stack_future::LocalStackFuture;
+///
+/// fn assert_send<T: Send>(_: T) {}
+///
+/// let future = LocalStackFuture::<_, 16>::new(async { std::rc::Rc::new(()) }).unwrap();
+/// let (_, vtable) = future.into_raw();
+/// assert_send(vtable);
+/// ```
+pub struct RawVTable<T> {
+    // Points to a promoted constant, so it is valid for the whole program. A
+    // raw pointer, since `T` may not be `'static`. This also makes the vtable
+    // neither Send nor Sync.
+    vtable: *const VTable<T>,
+    // Set if the inner future is known to be Send.
+    send: bool,
+}
+
+impl<T> RawVTable<T> {
+    /// Returns the vtable for a future of type `F`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn of<F: Future<Output = T>>() -> Self {
+        Self::new(VTable::new::<F>(), false)
+    }
+
+    fn new(vtable: &VTable<T>, send: bool) -> Self {
+        Self { vtable, send }
+    }
+
+    /// Returns the vtable, with the lifetime of the stack future it belongs to.
+    ///
+    /// Safety: `'a` must not outlive the lifetime of the original stack future.
+    unsafe fn get<'a>(self) -> &'a VTable<T> {
+        unsafe { &*self.vtable }
+    }
+
+    /// Converts the vtable into one that can be sent to another thread, if the
+    /// inner future is Send.
+    ///
+    /// This succeeds for the vtable of a [`StackFuture`], and of a
+    /// [`LocalStackFuture`] that was converted from one.
+    pub fn into_send(self) -> Result<SendRawVTable<T>, Self> {
+        if self.send {
+            Ok(SendRawVTable(self.vtable))
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<T> Clone for RawVTable<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
 
-impl<'a, T, const N: usize> fmt::Debug for LocalStackFuture<'a, T, N> {
+impl<T> Copy for RawVTable<T> {}
+
+impl<T> fmt::Debug for RawVTable<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("LocalStackFuture")
-            .field("size", &size_of::<Self>())
-            .field("alignment", &align_of::<Self>())
+        // Safe because the vtable is a promoted constant.
+        let vtable = unsafe { &*self.vtable };
+        f.debug_struct("RawVTable")
+            .field("stored_size", &vtable.size)
+            .field("stored_align", &vtable.align)
+            .field("send", &self.send)
             .finish()
     }
 }
 
-impl<'a, T, const N: usize> LocalStackFuture<'a, T, N> {
-    /// Creates a new stack future from a concrete future.
-    ///
-    /// Returns an error if the future is too large or has incompatible alignment.
-    pub fn new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
-        Ok(Self(StackFutureImpl::new(future)?, PhantomData))
+/// The vtable of a Send stack future, which can be moved to another thread.
+///
+/// Created by [`RawVTable::into_send`], and converted back with `into`.
+pub struct SendRawVTable<T>(*const VTable<T>);
+
+// Safety: the vtable only holds function pointers and sizes, and is only
+// created for Send futures.
+unsafe impl<T> Send for SendRawVTable<T> {}
+
+// Safety: the vtable is immutable, and gives no access to a future.
+unsafe impl<T> Sync for SendRawVTable<T> {}
+
+impl<T> From<SendRawVTable<T>> for RawVTable<T> {
+    fn from(vtable: SendRawVTable<T>) -> Self {
+        Self {
+            vtable: vtable.0,
+            send: true,
+        }
     }
+}
 
-    // Safe helper to access inner as pinned.
-    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackFutureImpl<'a, T, N>> {
-        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackFutureImpl>.
-        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+impl<T> Clone for SendRawVTable<T> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
 
-impl<'a, T, const N: usize> Future for LocalStackFuture<'a, T, N> {
-    type Output = T;
+impl<T> Copy for SendRawVTable<T> {}
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.inner().poll(cx)
+impl<T> fmt::Debug for SendRawVTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Safe because the vtable is a promoted constant.
+        let vtable = unsafe { &*self.0 };
+        f.debug_struct("SendRawVTable")
+            .field("stored_size", &vtable.size)
+            .field("stored_align", &vtable.align)
+            .finish()
     }
 }
 
 /// A stack-allocated future that erases the concrete type of the future.
 ///
-/// This is the Send version of the future.
+/// This is the non-Send version of the future.
+#[must_use = "futures do nothing unless awaited"]
 #[repr(transparent)]
-pub struct StackFuture<'a, T, const N: usize>(StackFutureImpl<'a, T, N>);
+pub struct LocalStackFuture<'a, T, const N: usize = 64, const ALIGN: usize = 16>(
+    StackFutureImpl<'a, T, N, ALIGN>,
+    PhantomData<*const ()>,
+)
+where
+    Align<ALIGN>: SupportedAlignment;
 
-impl<'a, T, const N: usize> std::fmt::Debug for StackFuture<'a, T, N> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("StackFutureSend")
-            .field("size", &size_of::<Self>())
-            .field("alignment", &align_of::<Self>())
-            .finish()
+impl<'a, T, const N: usize, const ALIGN: usize> fmt::Debug for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_debug("LocalStackFuture", f)
     }
 }
 
-impl<'a, T, const N: usize> StackFuture<'a, T, N> {
+impl<'a, T, const N: usize, const ALIGN: usize> LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
     /// Creates a new stack future from a concrete future.
     ///
     /// Returns an error if the future is too large or has incompatible alignment.
-    pub fn new<F: Future<Output = T> + Send + 'a>(future: F) -> Result<Self, CreateError> {
-        Ok(Self(StackFutureImpl::new(future)?))
+    pub fn new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new(future)?, PhantomData))
     }
 
-    // Safe helper to access inner as pinned.
-    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackFutureImpl<'a, T, N>> {
-        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackFutureImpl>.
-        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    /// Creates a new stack future from a value that can be turned into a future.
+    ///
+    /// The size and alignment checks apply to the future returned by
+    /// [`IntoFuture::into_future`], not to `I` itself.
+    pub fn from_into_future<I>(value: I) -> Result<Self, CreateError>
+    where
+        I: IntoFuture<Output = T>,
+        I::IntoFuture: 'a,
+    {
+        Self::new(value.into_future())
     }
-}
 
-impl<'a, T, const N: usize> Future for StackFuture<'a, T, N> {
-    type Output = T;
+    /// Creates a new stack future that places `future` in external `storage`.
+    ///
+    /// The buffer only holds a pointer to the storage, so `N` must fit a
+    /// pointer. Returns an error if the future does not fit into `storage`, or
+    /// if the start of `storage` is not sufficiently aligned for it.
+    ///
+    /// # Safety
+    ///
+    /// The stack future must be dropped before `storage` is reused, so it must
+    /// not be leaked with e.g. [`mem::forget`]. Otherwise the memory of a pinned
+    /// future could be overwritten without it being dropped.
+    pub unsafe fn new_in<F: Future<Output = T> + 'a>(
+        future: F,
+        storage: &'a mut [MaybeUninit<u8>],
+    ) -> Result<Self, CreateError> {
+        Self::new(Placed::new(future, storage)?)
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.inner().poll(cx)
+    /// Creates a new stack future, checking size and alignment at compile time.
+    ///
+    /// Unlike [`new`](Self::new), this can not fail at runtime. A future that
+    /// does not fit into the buffer is a compile-time error.
+    pub const fn new_exact<F: Future<Output = T> + 'a>(future: F) -> Self {
+        Self(StackFutureImpl::new_const(future), PhantomData)
     }
-}
 
-/// A stack-allocated future with a fixed-size, aligned buffer.
-///
-/// Safety: this hides the Send-ness of the inner future type, so it must not
-/// be publicly accessible outside of this crate.
-struct StackFutureImpl<'a, T, const N: usize> {
-    buffer: AlignedBuffer<N>,
-    vtable: &'a VTable<T>,
-    _pinned: PhantomPinned,
-}
+    /// Creates a new stack future from a zero-sized future in a const context.
+    ///
+    /// This can be used to build stack futures in `const` and `static` items.
+    /// Any zero-sized future is const-constructible this way, as long as the
+    /// future value itself can be created in a const context. Passing a future
+    /// that is not zero-sized is a compile-time error.
+    pub const fn new_zst<F: Future<Output = T> + 'a>(future: F) -> Self {
+        Self(StackFutureImpl::new_zst(future), PhantomData)
+    }
 
-impl<'a, T, const N: usize> StackFutureImpl<'a, T, N> {
-    pub fn new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
-        // Check if the future fits in the buffer and has compatible alignment.
-        if size_of::<F>() > N {
-            return Err(CreateError::SizeTooLarge {
-                size: size_of::<F>(),
-                max_size: N,
-            });
+    /// Creates a stack future that is immediately ready with `value`.
+    ///
+    /// Fails to compile if the value does not fit into the buffer.
+    pub fn from_ready(value: T) -> Self
+    where
+        T: 'a,
+    {
+        Self(StackFutureImpl::new_const(ready(value)), PhantomData)
+    }
+
+    /// Creates a stack future that never completes.
+    pub fn from_pending() -> Self
+    where
+        T: 'a,
+    {
+        Self::new_zst(pending())
+    }
+
+    /// Creates a new stack future from a poll function, like [`core::future::poll_fn`].
+    ///
+    /// The buffer must fit the state captured by the closure.
+    pub fn poll_fn<G: FnMut(&mut Context<'_>) -> Poll<T> + 'a>(g: G) -> Result<Self, CreateError> {
+        Self::new(poll_fn(g))
+    }
+
+    /// Creates a new stack future from a concrete future, handing the future
+    /// back on failure.
+    ///
+    /// This allows falling back to e.g. `Box::pin` without having to reconstruct
+    /// the future.
+    pub fn try_new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, (CreateError, F)> {
+        Ok(Self(StackFutureImpl::try_new(future)?, PhantomData))
+    }
+
+    /// Creates a new stack future without checking size and alignment.
+    ///
+    /// This skips the checks of [`new`](Self::new) for callers that already
+    /// made them at compile time, e.g. with [`stack_future_assert_fits!`](crate::stack_future_assert_fits).
+    ///
+    /// # Safety
+    ///
+    /// `size_of::<F>()` must be at most `N`, and `align_of::<F>()` must be at most
+    /// the buffer alignment. Otherwise the future is written past the end of the
+    /// buffer or misaligned, which is undefined behavior.
+    pub unsafe fn new_unchecked<F: Future<Output = T> + 'a>(future: F) -> Self {
+        Self(
+            unsafe { StackFutureImpl::new_unchecked(future) },
+            PhantomData,
+        )
+    }
+
+    /// Creates a new stack future, handling a future that does not fit as
+    /// given by `overflow`.
+    pub fn with_overflow<F: Future<Output = T> + 'a>(
+        future: F,
+        overflow: Overflow,
+    ) -> Result<Self, CreateError>
+    where
+        T: 'a,
+    {
+        Ok(Self(
+            StackFutureImpl::with_overflow(future, overflow)?,
+            PhantomData,
+        ))
+    }
+
+    /// Creates a new stack future, or boxes the future if it does not fit.
+    ///
+    /// Unlike [`SmallFuture`](crate::SmallFuture), the caller can observe which
+    /// storage was chosen.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn new_or_box<F: Future<Output = T> + 'a>(
+        future: F,
+    ) -> Either<Self, Pin<Box<dyn Future<Output = T> + 'a>>> {
+        match Self::try_new(future) {
+            Ok(future) => Either::Left(future),
+            Err((_, future)) => Either::Right(Box::pin(future)),
         }
+    }
 
-        if align_of::<F>() > align_of::<AlignedBuffer<N>>() {
-            return Err(CreateError::AlignmentMismatch {
-                alignment: align_of::<F>(),
-                expected: align_of::<AlignedBuffer<N>>(),
-            });
+    /// Creates a new stack future from a concrete fused future.
+    ///
+    /// Only stack futures created this way forward [`FusedFuture::is_terminated`]
+    /// to the inner future. All others report being terminated only once they
+    /// completed.
+    #[cfg(feature = "futures")]
+    pub fn new_fused<F: FusedFuture<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new_fused(future)?, PhantomData))
+    }
+
+    /// Creates a new stack future from a concrete future that can be cloned.
+    ///
+    /// Only stack futures created this way implement [`Clone::clone`], all
+    /// others panic. Cloning is meant for futures that were not polled yet, e.g.
+    /// to retry an operation, and is checked in debug builds.
+    pub fn new_cloneable<F: Future<Output = T> + Clone + 'a>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new_cloneable(future)?, PhantomData))
+    }
+
+    /// Clones the inner future, or returns `None` if it was not created with
+    /// [`new_cloneable`](Self::new_cloneable).
+    pub fn try_clone(&self) -> Option<Self> {
+        Some(Self(self.0.try_clone()?, PhantomData))
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        align_of::<AlignedBuffer<N, ALIGN>>()
+    }
+
+    /// Returns the size of the stack future beyond its buffer, in bytes.
+    ///
+    /// This is the cost of nesting a stack future in another one, e.g. in
+    /// [`map`](Self::map), on top of its buffer size `N`. It is 32 bytes on
+    /// 64-bit targets with the default alignment: a pointer to the shared
+    /// vtable, the poll counts and two flags, padded to the buffer alignment.
+    pub const fn storage_overhead() -> usize {
+        size_of::<Self>() - N
+    }
+
+    /// Returns the buffer size needed for the future returned by `probe`.
+    ///
+    /// The future type is inferred from the closure, which is never called.
+    /// This helps to pick `N` for the unnameable futures of `async fn`.
+    pub fn sized_for<F: Future<Output = T>>(probe: impl FnOnce() -> F) -> usize {
+        drop(probe);
+        size_of::<F>()
+    }
+
+    /// Returns the size of the stored future.
+    pub fn stored_size(&self) -> usize {
+        self.0.vtable.size
+    }
+
+    /// Returns the alignment of the stored future.
+    pub fn stored_align(&self) -> usize {
+        self.0.vtable.align
+    }
+
+    /// Returns the buffer layout of this stack future, e.g. for logging.
+    pub fn info(&self) -> StackFutureInfo {
+        StackFutureInfo {
+            capacity: N,
+            stored_size: self.0.vtable.size,
+            stored_align: self.0.vtable.align,
+            storage: Storage::Inline,
+        }
+    }
+
+    /// Returns the fraction of the buffer used by the inner future, from 0 to 1.
+    ///
+    /// This helps to find buffers that are larger than needed. A zero-length
+    /// buffer counts as fully used.
+    pub fn utilization(&self) -> f32 {
+        if N == 0 {
+            return 1.0;
         }
+        self.0.vtable.size as f32 / N as f32
+    }
 
-        // Create the vtable for the future type.
-        let vtable = VTable::new::<F>();
+    /// Returns how often the inner future has been polled.
+    ///
+    /// The count starts over when the future is replaced, e.g. by [`rearm`](Self::rearm).
+    pub fn poll_count(&self) -> u64 {
+        self.0.polls
+    }
 
-        // Initialize the buffer with zeros.
-        let mut buffer = AlignedBuffer { buffer: [0u8; N] };
+    /// Returns how often the inner future has been polled since the last call
+    /// to [`reset_poll_count`](Self::reset_poll_count), saturating at `u32::MAX`.
+    ///
+    /// This is meant for schedulers that deprioritize tasks that were polled
+    /// many times without completing.
+    pub fn polls_since_reset(&self) -> u32 {
+        self.0.polls_since_reset
+    }
 
-        // Move the future into the buffer.
-        unsafe {
-            ptr::write(buffer.buffer.as_mut_ptr() as *mut F, future);
+    /// Resets the count returned by [`polls_since_reset`](Self::polls_since_reset).
+    ///
+    /// This does not affect [`poll_count`](Self::poll_count).
+    pub fn reset_poll_count(self: Pin<&mut Self>) {
+        self.inner().reset_poll_count();
+    }
+
+    /// Returns true if the inner future has returned its output.
+    ///
+    /// Polling a completed stack future returns `Poll::Pending` forever, without
+    /// polling the inner future again.
+    pub fn is_completed(&self) -> bool {
+        self.0.completed
+    }
+
+    /// Returns a reference to the stored future if it has type `F`.
+    ///
+    /// This is meant for inspecting the state of a known future type, e.g. for
+    /// debugging.
+    ///
+    /// # Safety
+    ///
+    /// Type ids do not distinguish lifetimes, so if `F` has lifetime parameters,
+    /// the stored future must have been created with exactly these lifetimes.
+    pub unsafe fn downcast_ref<F>(&self) -> Option<&F> {
+        unsafe { self.0.downcast_ref() }
+    }
+
+    /// Stores an already boxed future, keeping only the box in the buffer.
+    ///
+    /// The future itself stays on the heap. The buffer must fit the box, which
+    /// is two pointers wide; this is checked at compile time.
+    #[cfg(feature = "alloc")]
+    pub fn from_boxed(future: Pin<Box<dyn Future<Output = T> + 'a>>) -> Self
+    where
+        T: 'a,
+    {
+        Self::new_exact(future)
+    }
+
+    /// Boxes the future, for use with APIs that require `Unpin`.
+    #[cfg(feature = "alloc")]
+    pub fn boxed_local(self) -> Pin<Box<dyn Future<Output = T> + 'a>>
+    where
+        T: 'a,
+    {
+        Box::pin(self)
+    }
+
+    /// Polls the inner future, or returns `Ready(None)` if it already completed.
+    ///
+    /// Unlike [`Future::poll`], which returns `Poll::Pending` past completion,
+    /// this reports completion to the caller.
+    pub fn take_output(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner().take_output(cx)
+    }
+
+    /// Polls the future, writing its output to `out` instead of returning it.
+    ///
+    /// This is a convenience for callers that collect outputs into slots. The
+    /// output is still returned by the inner future and then moved into `out`.
+    pub fn poll_into(self: Pin<&mut Self>, cx: &mut Context<'_>, out: &mut Option<T>) -> Poll<()> {
+        self.inner().poll_into(cx, out)
+    }
+
+    /// Polls the future through a pinned reference, like [`Future::poll`].
+    ///
+    /// This is meant for structs that embed a stack future and reach it through
+    /// a pin projection, and works without importing [`Future`].
+    pub fn project_poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.inner().poll(cx)
+    }
+
+    /// Polls the future once with a no-op waker, returning the output if it is
+    /// ready right away.
+    ///
+    /// Otherwise the future is dropped and `None` is returned.
+    pub fn now_or_never(self) -> Option<T> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(self).poll(&mut cx) {
+            Poll::Ready(output) => Some(output),
+            Poll::Pending => None,
         }
+    }
 
-        Ok(Self {
-            buffer,
-            vtable,
-            _pinned: PhantomPinned,
-        })
+    /// Views this stack future as a pinned trait object, without boxing.
+    ///
+    /// This is for APIs that take `Pin<&mut dyn Future>`. Polls go through the
+    /// stack future itself, so they are counted and tracked as usual.
+    pub fn as_dyn(self: Pin<&mut Self>) -> Pin<&mut (dyn Future<Output = T> + 'a)>
+    where
+        T: 'a,
+    {
+        self
+    }
+
+    /// Polls the future without requiring it to be pinned.
+    ///
+    /// This is useful for hand-rolled executors that keep futures in place by value.
+    ///
+    /// # Safety
+    ///
+    /// The future must not be moved after the first call to this method, or
+    /// after being polled in any other way, until it is dropped.
+    pub unsafe fn poll_unpin(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        unsafe { Pin::new_unchecked(self) }.poll(cx)
+    }
+
+    /// Replaces the inner future with a new one, reusing the buffer.
+    ///
+    /// The previous future is dropped in place. Returns an error, leaving the
+    /// previous future untouched, if the new future does not fit.
+    pub fn rearm<F: Future<Output = T> + 'a>(
+        self: Pin<&mut Self>,
+        future: F,
+    ) -> Result<(), CreateError>
+    where
+        T: 'a,
+    {
+        self.inner().rearm(future, false)
+    }
+
+    /// Polls the inner future and, once it completes, replaces it with `next()`.
+    ///
+    /// The old future is dropped before `next` is called. If the new future
+    /// does not fit, the output is still returned, but the stack future panics
+    /// when polled again.
+    pub fn poll_replace<F: Future<Output = T> + 'a>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        next: impl FnOnce() -> F,
+    ) -> Poll<T>
+    where
+        T: 'a,
+    {
+        self.inner().poll_replace(cx, next, false)
+    }
+
+    /// Converts this into a [`StackFuture`] if the inner future is Send.
+    ///
+    /// This only succeeds if the inner future was passed through a constructor
+    /// that requires Send, e.g. if this was converted from a [`StackFuture`].
+    /// Otherwise the future is handed back unchanged.
+    pub fn try_into_send(self) -> Result<StackFuture<'a, T, N, ALIGN>, Self> {
+        if self.0.send {
+            Ok(StackFuture(self.0))
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Splits the stack future into its buffer and its vtable.
+    ///
+    /// The inner future is not dropped. Use `from_raw` to reassemble the stack
+    /// future, otherwise the inner future is leaked.
+    pub fn into_raw(self) -> ([MaybeUninit<u8>; N], RawVTable<T>) {
+        let send = self.0.send;
+        let (buffer, vtable) = self.0.into_raw();
+        (buffer, RawVTable::new(vtable, send))
+    }
+
+    /// Reassembles a stack future from the parts returned by `into_raw`.
+    ///
+    /// The buffer is moved into an aligned buffer, so it can be stored unaligned
+    /// in between. The poll count starts over.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` and `vtable` must come from the same call to `into_raw` on a
+    /// stack future of this type, and each pair may be reassembled only once.
+    /// `'a` must not outlive the lifetime of the original stack future. Unless
+    /// the vtable went through [`RawVTable::into_send`], the parts must be
+    /// reassembled on the thread that called `into_raw`.
+    pub unsafe fn from_raw(buffer: [MaybeUninit<u8>; N], vtable: RawVTable<T>) -> Self {
+        Self(
+            unsafe { StackFutureImpl::from_raw(buffer, vtable.get()) },
+            PhantomData,
+        )
+    }
+
+    /// Moves the inner future into a buffer of size `M`.
+    ///
+    /// This is meant for shrinking a stack future that was sized for the worst
+    /// case. If the inner future does not fit, it is handed back unchanged.
+    pub fn downsize<const M: usize>(self) -> Result<LocalStackFuture<'a, T, M, ALIGN>, Self> {
+        match self.0.resize() {
+            Ok(inner) => Ok(LocalStackFuture(inner, PhantomData)),
+            Err(inner) => Err(Self(inner, PhantomData)),
+        }
+    }
+
+    /// Moves the inner future into a buffer of size `M`, which may be larger or
+    /// smaller than `N`.
+    ///
+    /// Only the bytes of the inner future are copied. If it does not fit, the
+    /// error is returned together with the unchanged stack future.
+    ///
+    /// Taking `self` by value guarantees that the stack future is not pinned. A
+    /// stack future polled with [`poll_unpin`](Self::poll_unpin) must not be
+    /// resized, as it must not be moved at all.
+    pub fn resize<const M: usize>(
+        self,
+    ) -> Result<LocalStackFuture<'a, T, M, ALIGN>, (CreateError, Self)> {
+        let error = self.0.size_error(M);
+        self.downsize().map_err(|this| (error, this))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackFutureImpl<'a, T, N, ALIGN>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackFutureImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+/// Clones the inner future.
+///
+/// # Panics
+///
+/// Panics if the stack future was not created with
+/// [`new_cloneable`](LocalStackFuture::new_cloneable).
+impl<'a, T, const N: usize, const ALIGN: usize> Clone for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn clone(&self) -> Self {
+        self.try_clone()
+            .expect("stack future was not created with new_cloneable")
+    }
+}
+
+/// Creates a stack future that never completes, see [`from_pending`](LocalStackFuture::from_pending).
+///
+/// This is useful as a placeholder, to be replaced later with [`rearm`](LocalStackFuture::rearm).
+impl<'a, T: 'a, const N: usize, const ALIGN: usize> Default for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn default() -> Self {
+        Self::from_pending()
     }
 }
 
-impl<'a, T, const N: usize> Future for StackFutureImpl<'a, T, N> {
+impl<'a, T, const N: usize, const ALIGN: usize> Future for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
     type Output = T;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        unsafe {
-            let this = self.get_unchecked_mut();
-            (this.vtable.poll)(this.buffer.buffer.as_mut_ptr(), cx)
-        }
+        self.inner().poll(cx)
     }
 }
 
-impl<'a, T, const N: usize> Drop for StackFutureImpl<'a, T, N> {
-    fn drop(&mut self) {
-        unsafe {
-            (self.vtable.drop)(self.buffer.buffer.as_mut_ptr());
+#[cfg(feature = "futures")]
+impl<'a, T, const N: usize, const ALIGN: usize> FusedFuture for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn is_terminated(&self) -> bool {
+        self.0.is_terminated()
+    }
+}
+
+/// A stack-allocated future that erases the concrete type of the future.
+///
+/// This is the Send version of the future.
+///
+/// The inner future may borrow data that lives for `'a`, and the stack future
+/// can not outlive that data:
+///
+/// ```compile_fail
+/// use stack_future::StackFuture;
+///
+/// let future = {
+///     let data = vec![1u64, 2, 3];
+///     let slice = data.as_slice();
+///     StackFuture::<_, 64>::new(async move { slice.iter().sum::<u64>() }).unwrap()
+/// };
+/// ```
+///
+/// The inner future must be Send, use [`LocalStackFuture`] otherwise:
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use stack_future::StackFuture;
+///
+/// async fn non_send_future() -> u64 {
+///     let rc = Rc::new(42);
+///     async {}.await;
+///     *rc
+/// }
+///
+/// let future = StackFuture::<_, 32>::new(non_send_future());
+/// ```
+#[must_use = "futures do nothing unless awaited"]
+#[repr(transparent)]
+pub struct StackFuture<'a, T, const N: usize = 64, const ALIGN: usize = 16>(
+    StackFutureImpl<'a, T, N, ALIGN>,
+)
+where
+    Align<ALIGN>: SupportedAlignment;
+
+/// A [`StackFuture`] that does not borrow anything, e.g. for storing in a static.
+pub type StaticStackFuture<T, const N: usize = 64, const ALIGN: usize = 16> =
+    StackFuture<'static, T, N, ALIGN>;
+
+impl<T, const N: usize, const ALIGN: usize> StackFuture<'static, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new `'static` stack future from a concrete future.
+    ///
+    /// This is [`new`](Self::new) with the `'static` bound spelled out, so that a
+    /// borrowing future is rejected at the call site.
+    pub fn new_static<F: Future<Output = T> + Send + 'static>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Self::new(future)
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> fmt::Debug for StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_debug("StackFuture", f)
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new stack future from a concrete future.
+    ///
+    /// Returns an error if the future is too large or has incompatible alignment.
+    pub fn new<F: Future<Output = T> + Send + 'a>(future: F) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new(future)?.mark_send()))
+    }
+
+    /// Creates a new stack future from a value that can be turned into a Send future.
+    ///
+    /// The size and alignment checks apply to the future returned by
+    /// [`IntoFuture::into_future`], not to `I` itself.
+    pub fn from_into_future<I>(value: I) -> Result<Self, CreateError>
+    where
+        I: IntoFuture<Output = T>,
+        I::IntoFuture: Send + 'a,
+    {
+        Self::new(value.into_future())
+    }
+
+    /// Creates a new stack future that places `future` in external `storage`.
+    ///
+    /// The buffer only holds a pointer to the storage, so `N` must fit a
+    /// pointer. Returns an error if the future does not fit into `storage`, or
+    /// if the start of `storage` is not sufficiently aligned for it.
+    ///
+    /// # Safety
+    ///
+    /// The stack future must be dropped before `storage` is reused, so it must
+    /// not be leaked with e.g. [`mem::forget`]. Otherwise the memory of a pinned
+    /// future could be overwritten without it being dropped.
+    pub unsafe fn new_in<F: Future<Output = T> + Send + 'a>(
+        future: F,
+        storage: &'a mut [MaybeUninit<u8>],
+    ) -> Result<Self, CreateError> {
+        Self::new(Placed::new(future, storage)?)
+    }
+
+    /// Creates a new stack future, checking size and alignment at compile time.
+    ///
+    /// Unlike [`new`](Self::new), this can not fail at runtime. A future that
+    /// does not fit into the buffer is a compile-time error.
+    pub const fn new_exact<F: Future<Output = T> + Send + 'a>(future: F) -> Self {
+        Self(StackFutureImpl::new_const(future).mark_send())
+    }
+
+    /// Creates a new stack future from a zero-sized future in a const context.
+    ///
+    /// This can be used to build stack futures in `const` and `static` items.
+    /// Any zero-sized future is const-constructible this way, as long as the
+    /// future value itself can be created in a const context. Passing a future
+    /// that is not zero-sized is a compile-time error.
+    pub const fn new_zst<F: Future<Output = T> + Send + 'a>(future: F) -> Self {
+        Self(StackFutureImpl::new_zst(future).mark_send())
+    }
+
+    /// Creates a stack future that is immediately ready with `value`.
+    ///
+    /// Fails to compile if the value does not fit into the buffer.
+    pub fn from_ready(value: T) -> Self
+    where
+        T: Send + 'a,
+    {
+        Self(StackFutureImpl::new_const(ready(value)).mark_send())
+    }
+
+    /// Creates a stack future that never completes.
+    pub fn from_pending() -> Self
+    where
+        T: 'a,
+    {
+        Self::new_zst(pending())
+    }
+
+    /// Creates a new stack future from a poll function, like [`core::future::poll_fn`].
+    ///
+    /// The buffer must fit the state captured by the closure.
+    pub fn poll_fn<G: FnMut(&mut Context<'_>) -> Poll<T> + Send + 'a>(
+        g: G,
+    ) -> Result<Self, CreateError> {
+        Self::new(poll_fn(g))
+    }
+
+    /// Creates a new stack future from a concrete future, handing the future
+    /// back on failure.
+    ///
+    /// This allows falling back to e.g. `Box::pin` without having to reconstruct
+    /// the future.
+    pub fn try_new<F: Future<Output = T> + Send + 'a>(future: F) -> Result<Self, (CreateError, F)> {
+        Ok(Self(StackFutureImpl::try_new(future)?.mark_send()))
+    }
+
+    /// Creates a new stack future without checking size and alignment.
+    ///
+    /// This skips the checks of [`new`](Self::new) for callers that already
+    /// made them at compile time, e.g. with [`stack_future_assert_fits!`](crate::stack_future_assert_fits).
+    ///
+    /// # Safety
+    ///
+    /// `size_of::<F>()` must be at most `N`, and `align_of::<F>()` must be at most
+    /// the buffer alignment. Otherwise the future is written past the end of the
+    /// buffer or misaligned, which is undefined behavior.
+    pub unsafe fn new_unchecked<F: Future<Output = T> + Send + 'a>(future: F) -> Self {
+        Self(unsafe { StackFutureImpl::new_unchecked(future) }.mark_send())
+    }
+
+    /// Creates a new stack future, handling a future that does not fit as
+    /// given by `overflow`.
+    pub fn with_overflow<F: Future<Output = T> + Send + 'a>(
+        future: F,
+        overflow: Overflow,
+    ) -> Result<Self, CreateError>
+    where
+        T: 'a,
+    {
+        Ok(Self(
+            StackFutureImpl::with_overflow(future, overflow)?.mark_send(),
+        ))
+    }
+
+    /// Creates a new stack future, or boxes the future if it does not fit.
+    ///
+    /// Unlike [`SmallFuture`](crate::SmallFuture), the caller can observe which
+    /// storage was chosen.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn new_or_box<F: Future<Output = T> + Send + 'a>(
+        future: F,
+    ) -> Either<Self, Pin<Box<dyn Future<Output = T> + Send + 'a>>> {
+        match Self::try_new(future) {
+            Ok(future) => Either::Left(future),
+            Err((_, future)) => Either::Right(Box::pin(future)),
+        }
+    }
+
+    /// Creates a new stack future from a concrete fused future.
+    ///
+    /// Only stack futures created this way forward [`FusedFuture::is_terminated`]
+    /// to the inner future. All others report being terminated only once they
+    /// completed.
+    ///
+    /// `F` must be Sync, since `is_terminated` takes `&self` and the stack
+    /// future can be shared across threads.
+    #[cfg(feature = "futures")]
+    pub fn new_fused<F: FusedFuture<Output = T> + Send + Sync + 'a>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new_fused(future)?.mark_send()))
+    }
+
+    /// Creates a new stack future from a concrete future that can be cloned.
+    ///
+    /// Only stack futures created this way implement [`Clone::clone`], all
+    /// others panic. Cloning is meant for futures that were not polled yet, e.g.
+    /// to retry an operation, and is checked in debug builds.
+    ///
+    /// `F` must be Sync, since `clone` takes `&self` and the stack future can be
+    /// shared across threads.
+    pub fn new_cloneable<F: Future<Output = T> + Clone + Send + Sync + 'a>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new_cloneable(future)?.mark_send()))
+    }
+
+    /// Clones the inner future, or returns `None` if it was not created with
+    /// [`new_cloneable`](Self::new_cloneable).
+    pub fn try_clone(&self) -> Option<Self> {
+        Some(Self(self.0.try_clone()?))
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        align_of::<AlignedBuffer<N, ALIGN>>()
+    }
+
+    /// Returns the size of the stack future beyond its buffer, in bytes.
+    ///
+    /// This is the cost of nesting a stack future in another one, e.g. in
+    /// [`map`](Self::map), on top of its buffer size `N`. It is 32 bytes on
+    /// 64-bit targets with the default alignment: a pointer to the shared
+    /// vtable, the poll counts and two flags, padded to the buffer alignment.
+    pub const fn storage_overhead() -> usize {
+        size_of::<Self>() - N
+    }
+
+    /// Returns the buffer size needed for the future returned by `probe`.
+    ///
+    /// The future type is inferred from the closure, which is never called.
+    /// This helps to pick `N` for the unnameable futures of `async fn`.
+    pub fn sized_for<F: Future<Output = T>>(probe: impl FnOnce() -> F) -> usize {
+        drop(probe);
+        size_of::<F>()
+    }
+
+    /// Returns the size of the stored future.
+    pub fn stored_size(&self) -> usize {
+        self.0.vtable.size
+    }
+
+    /// Returns the alignment of the stored future.
+    pub fn stored_align(&self) -> usize {
+        self.0.vtable.align
+    }
+
+    /// Returns the buffer layout of this stack future, e.g. for logging.
+    pub fn info(&self) -> StackFutureInfo {
+        StackFutureInfo {
+            capacity: N,
+            stored_size: self.0.vtable.size,
+            stored_align: self.0.vtable.align,
+            storage: Storage::Inline,
+        }
+    }
+
+    /// Returns the fraction of the buffer used by the inner future, from 0 to 1.
+    ///
+    /// This helps to find buffers that are larger than needed. A zero-length
+    /// buffer counts as fully used.
+    pub fn utilization(&self) -> f32 {
+        if N == 0 {
+            return 1.0;
+        }
+        self.0.vtable.size as f32 / N as f32
+    }
+
+    /// Returns how often the inner future has been polled.
+    ///
+    /// The count starts over when the future is replaced, e.g. by [`rearm`](Self::rearm).
+    pub fn poll_count(&self) -> u64 {
+        self.0.polls
+    }
+
+    /// Returns how often the inner future has been polled since the last call
+    /// to [`reset_poll_count`](Self::reset_poll_count), saturating at `u32::MAX`.
+    ///
+    /// This is meant for schedulers that deprioritize tasks that were polled
+    /// many times without completing.
+    pub fn polls_since_reset(&self) -> u32 {
+        self.0.polls_since_reset
+    }
+
+    /// Resets the count returned by [`polls_since_reset`](Self::polls_since_reset).
+    ///
+    /// This does not affect [`poll_count`](Self::poll_count).
+    pub fn reset_poll_count(self: Pin<&mut Self>) {
+        self.inner().reset_poll_count();
+    }
+
+    /// Returns true if the inner future has returned its output.
+    ///
+    /// Polling a completed stack future returns `Poll::Pending` forever, without
+    /// polling the inner future again.
+    pub fn is_completed(&self) -> bool {
+        self.0.completed
+    }
+
+    /// Returns a reference to the stored future if it has type `F`.
+    ///
+    /// This is meant for inspecting the state of a known future type, e.g. for
+    /// debugging.
+    ///
+    /// `F` must be Sync, since the stack future can be shared across threads.
+    ///
+    /// # Safety
+    ///
+    /// Type ids do not distinguish lifetimes, so if `F` has lifetime parameters,
+    /// the stored future must have been created with exactly these lifetimes.
+    pub unsafe fn downcast_ref<F: Sync>(&self) -> Option<&F> {
+        unsafe { self.0.downcast_ref() }
+    }
+
+    /// Stores an already boxed future, keeping only the box in the buffer.
+    ///
+    /// The future itself stays on the heap. The buffer must fit the box, which
+    /// is two pointers wide; this is checked at compile time.
+    #[cfg(feature = "alloc")]
+    pub fn from_boxed(future: Pin<Box<dyn Future<Output = T> + Send + 'a>>) -> Self
+    where
+        T: 'a,
+    {
+        Self::new_exact(future)
+    }
+
+    /// Boxes the future, for use with APIs that require `Unpin`.
+    #[cfg(feature = "alloc")]
+    pub fn boxed(self) -> Pin<Box<dyn Future<Output = T> + Send + 'a>>
+    where
+        T: 'a,
+    {
+        Box::pin(self)
+    }
+
+    /// Polls the inner future, or returns `Ready(None)` if it already completed.
+    ///
+    /// Unlike [`Future::poll`], which returns `Poll::Pending` past completion,
+    /// this reports completion to the caller.
+    pub fn take_output(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner().take_output(cx)
+    }
+
+    /// Polls the future, writing its output to `out` instead of returning it.
+    ///
+    /// This is a convenience for callers that collect outputs into slots. The
+    /// output is still returned by the inner future and then moved into `out`.
+    pub fn poll_into(self: Pin<&mut Self>, cx: &mut Context<'_>, out: &mut Option<T>) -> Poll<()> {
+        self.inner().poll_into(cx, out)
+    }
+
+    /// Polls the future through a pinned reference, like [`Future::poll`].
+    ///
+    /// This is meant for structs that embed a stack future and reach it through
+    /// a pin projection, and works without importing [`Future`].
+    pub fn project_poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.inner().poll(cx)
+    }
+
+    /// Polls the future once with a no-op waker, returning the output if it is
+    /// ready right away.
+    ///
+    /// Otherwise the future is dropped and `None` is returned.
+    pub fn now_or_never(self) -> Option<T> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(self).poll(&mut cx) {
+            Poll::Ready(output) => Some(output),
+            Poll::Pending => None,
+        }
+    }
+
+    /// Views this stack future as a pinned trait object, without boxing.
+    ///
+    /// This is for APIs that take `Pin<&mut dyn Future>`. Polls go through the
+    /// stack future itself, so they are counted and tracked as usual.
+    pub fn as_dyn(self: Pin<&mut Self>) -> Pin<&mut (dyn Future<Output = T> + Send + 'a)>
+    where
+        T: 'a,
+    {
+        self
+    }
+
+    /// Polls the future without requiring it to be pinned.
+    ///
+    /// This is useful for hand-rolled executors that keep futures in place by value.
+    ///
+    /// # Safety
+    ///
+    /// The future must not be moved after the first call to this method, or
+    /// after being polled in any other way, until it is dropped.
+    pub unsafe fn poll_unpin(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        unsafe { Pin::new_unchecked(self) }.poll(cx)
+    }
+
+    /// Replaces the inner future with a new one, reusing the buffer.
+    ///
+    /// The previous future is dropped in place. Returns an error, leaving the
+    /// previous future untouched, if the new future does not fit.
+    pub fn rearm<F: Future<Output = T> + Send + 'a>(
+        self: Pin<&mut Self>,
+        future: F,
+    ) -> Result<(), CreateError>
+    where
+        T: 'a,
+    {
+        self.inner().rearm(future, true)
+    }
+
+    /// Polls the inner future and, once it completes, replaces it with `next()`.
+    ///
+    /// The old future is dropped before `next` is called. If the new future
+    /// does not fit, the output is still returned, but the stack future panics
+    /// when polled again.
+    pub fn poll_replace<F: Future<Output = T> + Send + 'a>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        next: impl FnOnce() -> F,
+    ) -> Poll<T>
+    where
+        T: 'a,
+    {
+        self.inner().poll_replace(cx, next, true)
+    }
+
+    /// Splits the stack future into its buffer and its vtable.
+    ///
+    /// The inner future is not dropped. Use `from_raw` to reassemble the stack
+    /// future, otherwise the inner future is leaked.
+    pub fn into_raw(self) -> ([MaybeUninit<u8>; N], RawVTable<T>) {
+        let send = self.0.send;
+        let (buffer, vtable) = self.0.into_raw();
+        (buffer, RawVTable::new(vtable, send))
+    }
+
+    /// Reassembles a stack future from the parts returned by `into_raw`.
+    ///
+    /// The buffer is moved into an aligned buffer, so it can be stored unaligned
+    /// in between. The poll count starts over.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` and `vtable` must come from the same call to `into_raw` on a
+    /// stack future of this type, and each pair may be reassembled only once.
+    /// `'a` must not outlive the lifetime of the original stack future, and the
+    /// original must have been a [`StackFuture`], so that the inner future is Send.
+    pub unsafe fn from_raw(buffer: [MaybeUninit<u8>; N], vtable: RawVTable<T>) -> Self {
+        Self(unsafe { StackFutureImpl::from_raw(buffer, vtable.get()) }.mark_send())
+    }
+
+    /// Moves the inner future into a buffer of size `M`.
+    ///
+    /// This is meant for shrinking a stack future that was sized for the worst
+    /// case. If the inner future does not fit, it is handed back unchanged.
+    pub fn downsize<const M: usize>(self) -> Result<StackFuture<'a, T, M, ALIGN>, Self> {
+        self.0.resize().map(StackFuture).map_err(Self)
+    }
+
+    /// Moves the inner future into a buffer of size `M`, which may be larger or
+    /// smaller than `N`.
+    ///
+    /// Only the bytes of the inner future are copied. If it does not fit, the
+    /// error is returned together with the unchanged stack future.
+    ///
+    /// Taking `self` by value guarantees that the stack future is not pinned. A
+    /// stack future polled with [`poll_unpin`](Self::poll_unpin) must not be
+    /// resized, as it must not be moved at all.
+    pub fn resize<const M: usize>(
+        self,
+    ) -> Result<StackFuture<'a, T, M, ALIGN>, (CreateError, Self)> {
+        let error = self.0.size_error(M);
+        self.downsize().map_err(|this| (error, this))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackFutureImpl<'a, T, N, ALIGN>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackFutureImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> From<StackFuture<'a, T, N, ALIGN>>
+    for LocalStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn from(future: StackFuture<'a, T, N, ALIGN>) -> Self {
+        Self(future.0, PhantomData)
+    }
+}
+
+/// Polls every stack future in a pinned array in place, and collects the outputs
+/// of those that completed.
+///
+/// Stack futures that completed in an earlier call are not polled again, and
+/// their slots are `None`, like those of futures that are still pending.
+pub fn poll_all<'a, T, const N: usize, const ALIGN: usize, const K: usize>(
+    futures: Pin<&mut [StackFuture<'a, T, N, ALIGN>; K]>,
+    cx: &mut Context<'_>,
+) -> [Option<T>; K]
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    // Safe because the array is pinned, so each element is pinned as well, and
+    // no element is moved out of it.
+    let futures = unsafe { futures.get_unchecked_mut() };
+    let mut outputs = [const { None }; K];
+    for (future, output) in futures.iter_mut().zip(&mut outputs) {
+        let future = unsafe { Pin::new_unchecked(future) };
+        if let Poll::Ready(value) = future.take_output(cx) {
+            *output = value;
+        }
+    }
+    outputs
+}
+
+/// Clones the inner future.
+///
+/// # Panics
+///
+/// Panics if the stack future was not created with
+/// [`new_cloneable`](StackFuture::new_cloneable).
+impl<'a, T, const N: usize, const ALIGN: usize> Clone for StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn clone(&self) -> Self {
+        self.try_clone()
+            .expect("stack future was not created with new_cloneable")
+    }
+}
+
+/// Creates a stack future that never completes, see [`from_pending`](StackFuture::from_pending).
+///
+/// This is useful as a placeholder, to be replaced later with [`rearm`](StackFuture::rearm).
+impl<'a, T: 'a, const N: usize, const ALIGN: usize> Default for StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn default() -> Self {
+        Self::from_pending()
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> Future for StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner().poll(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<'a, T, const N: usize, const ALIGN: usize> FusedFuture for StackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn is_terminated(&self) -> bool {
+        self.0.is_terminated()
+    }
+}
+
+/// A stack-allocated future with a fixed-size, aligned buffer.
+///
+/// Safety: this hides the Send-ness of the inner future type, so it must not
+/// be publicly accessible outside of this crate.
+pub(crate) struct StackFutureImpl<'a, T, const N: usize, const ALIGN: usize>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    buffer: AlignedBuffer<N, ALIGN>,
+    vtable: &'a VTable<T>,
+    // Set once the inner future has returned `Poll::Ready`.
+    completed: bool,
+    // Set if the inner future is known to be Send.
+    send: bool,
+    // Number of times the inner future has been polled.
+    polls: u64,
+    // Like `polls`, but can be reset by a scheduler.
+    polls_since_reset: u32,
+    // Ties the erased future's lifetime to the stack future.
+    _lifetime: PhantomData<&'a ()>,
+    _pinned: PhantomPinned,
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> StackFutureImpl<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    pub fn new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
+        Self::try_new(future).map_err(|(error, _)| error)
+    }
+
+    /// Marks the inner future as Send, for constructors that require it.
+    const fn mark_send(mut self) -> Self {
+        self.send = true;
+        self
+    }
+
+    pub(crate) fn poll_count(&self) -> u64 {
+        self.polls
+    }
+
+    fn reset_poll_count(self: Pin<&mut Self>) {
+        // Safe because the counter is not structurally pinned.
+        unsafe { self.get_unchecked_mut() }.polls_since_reset = 0;
+    }
+
+    /// Formats the buffer layout and the stored future, for the wrappers' `Debug` impls.
+    pub(crate) fn fmt_debug(&self, name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(name)
+            .field("size", &size_of::<Self>())
+            .field("alignment", &align_of::<Self>())
+            .field("capacity", &N)
+            .field("stored_size", &self.vtable.size)
+            .field("stored_align", &self.vtable.align)
+            .finish()
+    }
+
+    const fn new_zst<F: Future<Output = T> + 'a>(future: F) -> Self {
+        const {
+            assert!(size_of::<F>() == 0, "future is not zero-sized");
+        }
+        Self::new_const(future)
+    }
+
+    /// Creates a new stack future, checking size and alignment at compile time.
+    const fn new_const<F: Future<Output = T> + 'a>(future: F) -> Self {
+        const {
+            assert!(size_of::<F>() <= N, "future does not fit into the buffer");
+            assert!(
+                align_of::<F>() <= align_of::<AlignedBuffer<N, ALIGN>>(),
+                "future alignment exceeds buffer alignment"
+            );
+        }
+        let mut buffer = AlignedBuffer::<N, ALIGN>::uninit();
+        unsafe {
+            ptr::write(buffer.buffer.as_mut_ptr().cast::<F>(), future);
+        }
+        Self {
+            buffer,
+            vtable: VTable::new::<F>(),
+            completed: false,
+            send: false,
+            polls: 0,
+            polls_since_reset: 0,
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    pub fn try_new<F: Future<Output = T> + 'a>(future: F) -> Result<Self, (CreateError, F)> {
+        // Check if the future fits in the buffer and has compatible alignment.
+        if let Err(error) = AlignedBuffer::<N, ALIGN>::check::<F>() {
+            return Err((error, future));
+        }
+
+        Ok(unsafe { Self::new_unchecked(future) })
+    }
+
+    /// Creates a new stack future without checking size and alignment.
+    ///
+    /// Safety: `F` must fit into the buffer and have compatible alignment.
+    unsafe fn new_unchecked<F: Future<Output = T> + 'a>(future: F) -> Self {
+        debug_assert!(AlignedBuffer::<N, ALIGN>::check::<F>().is_ok());
+
+        // Create the vtable for the future type.
+        let vtable = VTable::new::<F>();
+
+        // Leave the buffer uninitialized, the future is written over it below.
+        let mut buffer = AlignedBuffer::<N, ALIGN>::uninit();
+
+        // Move the future into the buffer.
+        unsafe {
+            ptr::write(buffer.buffer.as_mut_ptr().cast::<F>(), future);
+        }
+
+        Self {
+            buffer,
+            vtable,
+            completed: false,
+            send: false,
+            polls: 0,
+            polls_since_reset: 0,
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    fn into_raw(self) -> ([MaybeUninit<u8>; N], &'a VTable<T>) {
+        let this = mem::ManuallyDrop::new(self);
+        // Safe because `this` is never dropped, so the future is moved out once.
+        (unsafe { ptr::read(&this.buffer.buffer) }, this.vtable)
+    }
+
+    /// Safety: see [`LocalStackFuture::from_raw`].
+    unsafe fn from_raw(buffer: [MaybeUninit<u8>; N], vtable: &'a VTable<T>) -> Self {
+        let mut aligned = AlignedBuffer::<N, ALIGN>::uninit();
+        aligned.buffer = buffer;
+        Self {
+            buffer: aligned,
+            vtable,
+            completed: false,
+            send: false,
+            polls: 0,
+            polls_since_reset: 0,
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// Returns the error for storing the inner future in a buffer of size `max_size`.
+    fn size_error(&self, max_size: usize) -> CreateError {
+        CreateError::SizeTooLarge {
+            size: self.vtable.size,
+            max_size,
+            type_name: (self.vtable.type_info)().1,
+        }
+    }
+
+    /// Moves the future into a buffer of size `M`, if it fits.
+    fn resize<const M: usize>(self) -> Result<StackFutureImpl<'a, T, M, ALIGN>, Self> {
+        if self.vtable.size > M {
+            return Err(self);
+        }
+        let this = mem::ManuallyDrop::new(self);
+        let mut buffer = AlignedBuffer::<M, ALIGN>::uninit();
+        // Safe because the future is not pinned, and `this` is never dropped.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                this.buffer.buffer.as_ptr(),
+                buffer.buffer.as_mut_ptr(),
+                this.vtable.size,
+            );
+        }
+        Ok(StackFutureImpl {
+            buffer,
+            vtable: this.vtable,
+            completed: this.completed,
+            send: this.send,
+            polls: this.polls,
+            polls_since_reset: this.polls_since_reset,
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        })
+    }
+
+    fn with_overflow<F: Future<Output = T> + 'a>(
+        future: F,
+        overflow: Overflow,
+    ) -> Result<Self, CreateError>
+    where
+        T: 'a,
+    {
+        let (error, future) = match Self::try_new(future) {
+            Ok(this) => return Ok(this),
+            Err(err) => err,
+        };
+        match overflow {
+            Overflow::Error => Err(error),
+            #[cfg(feature = "alloc")]
+            Overflow::Box => Self::new(Box::pin(future)),
+            Overflow::Panic => {
+                drop(future);
+                panic!("{error}")
+            }
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    fn new_fused<F: FusedFuture<Output = T> + 'a>(future: F) -> Result<Self, CreateError> {
+        let mut this = Self::new(future)?;
+        this.vtable = VTable::new_fused::<F>();
+        Ok(this)
+    }
+
+    fn new_cloneable<F: Future<Output = T> + Clone + 'a>(future: F) -> Result<Self, CreateError> {
+        let mut this = Self::new(future)?;
+        this.vtable = VTable::new_cloneable::<F>();
+        Ok(this)
+    }
+
+    fn try_clone(&self) -> Option<Self> {
+        let clone = self.vtable.clone?;
+        debug_assert_eq!(self.polls, 0, "cloning a stack future that was polled");
+        let mut buffer = AlignedBuffer::<N, ALIGN>::uninit();
+        unsafe {
+            clone(
+                self.buffer.buffer.as_ptr().cast(),
+                buffer.buffer.as_mut_ptr().cast(),
+            );
+        }
+        Some(Self {
+            buffer,
+            vtable: self.vtable,
+            completed: self.completed,
+            send: self.send,
+            polls: 0,
+            polls_since_reset: 0,
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        })
+    }
+
+    unsafe fn downcast_ref<F>(&self) -> Option<&F> {
+        if (self.vtable.type_info)().0 == erased_type_id::<F>() {
+            Some(unsafe { &*self.buffer.buffer.as_ptr().cast::<F>() })
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    fn is_terminated(&self) -> bool {
+        // Completed futures are never polled again, whatever the inner future reports.
+        self.completed || unsafe { (self.vtable.is_terminated)(self.buffer.buffer.as_ptr().cast()) }
+    }
+
+    fn poll_into(self: Pin<&mut Self>, cx: &mut Context<'_>, out: &mut Option<T>) -> Poll<()> {
+        self.poll(cx).map(|output| *out = Some(output))
+    }
+
+    fn take_output(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if self.completed {
+            return Poll::Ready(None);
+        }
+        self.poll(cx).map(Some)
+    }
+
+    fn rearm<F: Future<Output = T> + 'a>(
+        self: Pin<&mut Self>,
+        future: F,
+        send: bool,
+    ) -> Result<(), CreateError>
+    where
+        T: 'a,
+    {
+        AlignedBuffer::<N, ALIGN>::check::<F>()?;
+        unsafe {
+            let this = self.get_unchecked_mut();
+            this.replace_unchecked(future);
+            this.send = send;
+        }
+        Ok(())
+    }
+
+    fn poll_replace<F: Future<Output = T> + 'a>(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        next: impl FnOnce() -> F,
+        send: bool,
+    ) -> Poll<T>
+    where
+        T: 'a,
+    {
+        let output = ready!(self.as_mut().poll(cx));
+        let this = unsafe { self.get_unchecked_mut() };
+        // Drop the old future before constructing the next one.
+        unsafe { this.replace_unchecked(Vacant(PhantomData)) };
+        if this.replace_with(next()).is_ok() {
+            this.send = send;
+        }
+        Poll::Ready(output)
+    }
+
+    /// Writes `future` into the buffer if it fits, without updating `send`.
+    fn replace_with<F: Future<Output = T> + 'a>(&mut self, future: F) -> Result<(), CreateError> {
+        AlignedBuffer::<N, ALIGN>::check::<F>()?;
+        unsafe { self.replace_unchecked(future) };
+        Ok(())
+    }
+
+    /// Drops the current future in place and writes `future` over it.
+    ///
+    /// Safety: `future` must fit into the buffer.
+    unsafe fn replace_unchecked<F: Future<Output = T> + 'a>(&mut self, future: F) {
+        let ptr = self.buffer.buffer.as_mut_ptr().cast();
+        // Leave the slot holding a future that needs no drop, in case dropping
+        // the old future panics.
+        let vtable = mem::replace(&mut self.vtable, VTable::new::<Pending<T>>());
+        unsafe {
+            (vtable.drop)(ptr);
+            ptr::write(ptr.cast::<F>(), future);
+        }
+        self.vtable = VTable::new::<F>();
+        self.completed = false;
+        self.polls = 0;
+        self.polls_since_reset = 0;
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> Future for StackFutureImpl<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe {
+            let this = self.get_unchecked_mut();
+            // Many futures panic or misbehave when polled past completion.
+            if this.completed {
+                return Poll::Pending;
+            }
+            this.polls += 1;
+            this.polls_since_reset = this.polls_since_reset.saturating_add(1);
+            let res = (this.vtable.poll)(this.buffer.buffer.as_mut_ptr().cast(), cx);
+            this.completed |= res.is_ready();
+            res
+        }
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> Drop for StackFutureImpl<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "detect_unpolled")]
+        // Pending placeholders, e.g. from `Default`, are meant to be replaced unpolled.
+        if cfg!(debug_assertions)
+            && self.polls == 0
+            && (self.vtable.type_info)().0 != erased_type_id::<Pending<T>>()
+        {
+            std::eprintln!("warning: stack future dropped without being polled");
+        }
+        unsafe {
+            (self.vtable.drop)(self.buffer.buffer.as_mut_ptr().cast());
+        }
+    }
+}
+
+/// Placeholder left behind by `poll_replace` while the slot holds no future.
+struct Vacant<T>(PhantomData<fn() -> T>);
+
+impl<T> Future for Vacant<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        panic!("stack future polled after its replacement did not fit")
+    }
+}
+
+/// A future placed in external storage by `new_in`, dropped in place with this.
+struct Placed<'buf, F>(&'buf mut F);
+
+impl<'buf, F> Placed<'buf, F> {
+    fn new(future: F, storage: &'buf mut [MaybeUninit<u8>]) -> Result<Self, CreateError> {
+        if size_of::<F>() > storage.len() {
+            return Err(CreateError::SizeTooLarge {
+                size: size_of::<F>(),
+                max_size: storage.len(),
+                type_name: type_name::<F>(),
+            });
+        }
+        let ptr = storage.as_mut_ptr().cast::<F>();
+        if !ptr.is_aligned() {
+            return Err(CreateError::AlignmentMismatch {
+                alignment: align_of::<F>(),
+                expected: 1 << ptr.addr().trailing_zeros(),
+                type_name: type_name::<F>(),
+            });
         }
+        unsafe {
+            ptr::write(ptr, future);
+            Ok(Self(&mut *ptr))
+        }
+    }
+}
+
+impl<'buf, F: Future> Future for Placed<'buf, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the future is never moved out of the storage.
+        unsafe { Pin::new_unchecked(&mut *self.get_mut().0) }.poll(cx)
+    }
+}
+
+impl<'buf, F> Drop for Placed<'buf, F> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.0) };
     }
 }
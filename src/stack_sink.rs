@@ -0,0 +1,254 @@
+//! A stack-allocated sink with a fixed-size, aligned buffer.
+//!
+//! This is the [`Sink`] counterpart of [`StackStream`](crate::StackStream).
+//! Creating a [`StackSink`] will fail if the sink is too large or has too big
+//! alignment requirements.
+use core::{
+    fmt,
+    marker::{PhantomData, PhantomPinned},
+    mem::{align_of, size_of},
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+
+use futures_sink::Sink;
+
+use crate::{AlignedBuffer, CreateError, drop_shim};
+
+struct SinkVTable<Item, E> {
+    poll_ready: unsafe fn(*mut u8, cx: &mut Context<'_>) -> Poll<Result<(), E>>,
+    start_send: unsafe fn(*mut u8, item: Item) -> Result<(), E>,
+    poll_flush: unsafe fn(*mut u8, cx: &mut Context<'_>) -> Poll<Result<(), E>>,
+    poll_close: unsafe fn(*mut u8, cx: &mut Context<'_>) -> Poll<Result<(), E>>,
+    drop: unsafe fn(*mut u8),
+}
+
+// Manual impls, since deriving would require `Item: Copy` and `E: Copy`.
+impl<Item, E> Clone for SinkVTable<Item, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Item, E> Copy for SinkVTable<Item, E> {}
+
+impl<Item, E> SinkVTable<Item, E> {
+    fn new<S: Sink<Item, Error = E>>() -> Self {
+        Self {
+            poll_ready: poll_ready_shim::<S, Item>,
+            start_send: start_send_shim::<S, Item>,
+            poll_flush: poll_flush_shim::<S, Item>,
+            poll_close: poll_close_shim::<S, Item>,
+            drop: drop_shim::<S>,
+        }
+    }
+}
+
+// Named and `#[inline]` like the future vtable shims, for readable backtraces.
+
+/// Polls the erased sink of type `S` at `ptr` until it is ready for an item.
+#[inline]
+unsafe fn poll_ready_shim<S: Sink<Item>, Item>(
+    ptr: *mut u8,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), S::Error>> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut S)) }.poll_ready(cx)
+}
+
+/// Sends an item to the erased sink of type `S` at `ptr`.
+#[inline]
+unsafe fn start_send_shim<S: Sink<Item>, Item>(ptr: *mut u8, item: Item) -> Result<(), S::Error> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut S)) }.start_send(item)
+}
+
+/// Flushes the erased sink of type `S` at `ptr`.
+#[inline]
+unsafe fn poll_flush_shim<S: Sink<Item>, Item>(
+    ptr: *mut u8,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), S::Error>> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut S)) }.poll_flush(cx)
+}
+
+/// Closes the erased sink of type `S` at `ptr`.
+#[inline]
+unsafe fn poll_close_shim<S: Sink<Item>, Item>(
+    ptr: *mut u8,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), S::Error>> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut S)) }.poll_close(cx)
+}
+
+/// A stack-allocated sink that erases the concrete type of the sink.
+///
+/// This is the non-Send version of the sink.
+#[repr(transparent)]
+pub struct LocalStackSink<'a, Item, E, const N: usize = 64>(
+    StackSinkImpl<'a, Item, E, N>,
+    PhantomData<*const ()>,
+);
+
+impl<'a, Item, E, const N: usize> fmt::Debug for LocalStackSink<'a, Item, E, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalStackSink")
+            .field("size", &size_of::<Self>())
+            .field("alignment", &align_of::<Self>())
+            .finish()
+    }
+}
+
+impl<'a, Item, E, const N: usize> LocalStackSink<'a, Item, E, N> {
+    /// Creates a new stack sink from a concrete sink.
+    ///
+    /// Returns an error if the sink is too large or has incompatible alignment.
+    pub fn new<S: Sink<Item, Error = E> + 'a>(sink: S) -> Result<Self, CreateError> {
+        Ok(Self(StackSinkImpl::new(sink)?, PhantomData))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackSinkImpl<'a, Item, E, N>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackSinkImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+impl<'a, Item, E, const N: usize> Sink<Item> for LocalStackSink<'a, Item, E, N> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), E> {
+        self.inner().start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_close(cx)
+    }
+}
+
+/// A stack-allocated sink that erases the concrete type of the sink.
+///
+/// This is the Send version of the sink.
+#[repr(transparent)]
+pub struct StackSink<'a, Item, E, const N: usize = 64>(StackSinkImpl<'a, Item, E, N>);
+
+impl<'a, Item, E, const N: usize> fmt::Debug for StackSink<'a, Item, E, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackSink")
+            .field("size", &size_of::<Self>())
+            .field("alignment", &align_of::<Self>())
+            .finish()
+    }
+}
+
+impl<'a, Item, E, const N: usize> StackSink<'a, Item, E, N> {
+    /// Creates a new stack sink from a concrete sink.
+    ///
+    /// Returns an error if the sink is too large or has incompatible alignment.
+    pub fn new<S: Sink<Item, Error = E> + Send + 'a>(sink: S) -> Result<Self, CreateError> {
+        Ok(Self(StackSinkImpl::new(sink)?))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackSinkImpl<'a, Item, E, N>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackSinkImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+impl<'a, Item, E, const N: usize> Sink<Item> for StackSink<'a, Item, E, N> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), E> {
+        self.inner().start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.inner().poll_close(cx)
+    }
+}
+
+/// A stack-allocated sink with a fixed-size, aligned buffer.
+///
+/// Safety: this hides the Send-ness of the inner sink type, so it must not
+/// be publicly accessible outside of this crate.
+struct StackSinkImpl<'a, Item, E, const N: usize> {
+    buffer: AlignedBuffer<N>,
+    vtable: SinkVTable<Item, E>,
+    // Ties the erased sink's lifetime to the stack sink.
+    _lifetime: PhantomData<&'a ()>,
+    _pinned: PhantomPinned,
+}
+
+impl<'a, Item, E, const N: usize> StackSinkImpl<'a, Item, E, N> {
+    fn new<S: Sink<Item, Error = E> + 'a>(sink: S) -> Result<Self, CreateError> {
+        AlignedBuffer::<N>::check::<S>()?;
+
+        let mut buffer = AlignedBuffer::uninit();
+
+        // Move the sink into the buffer.
+        unsafe {
+            ptr::write(buffer.buffer.as_mut_ptr().cast::<S>(), sink);
+        }
+
+        Ok(Self {
+            buffer,
+            vtable: SinkVTable::new::<S>(),
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        })
+    }
+
+    fn as_mut_ptr(self: Pin<&mut Self>) -> (*mut u8, SinkVTable<Item, E>) {
+        // Safe because the sink is only ever accessed in place.
+        let this = unsafe { self.get_unchecked_mut() };
+        (this.buffer.buffer.as_mut_ptr().cast(), this.vtable)
+    }
+}
+
+impl<'a, Item, E, const N: usize> Sink<Item> for StackSinkImpl<'a, Item, E, N> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        let (ptr, vtable) = self.as_mut_ptr();
+        unsafe { (vtable.poll_ready)(ptr, cx) }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), E> {
+        let (ptr, vtable) = self.as_mut_ptr();
+        unsafe { (vtable.start_send)(ptr, item) }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        let (ptr, vtable) = self.as_mut_ptr();
+        unsafe { (vtable.poll_flush)(ptr, cx) }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        let (ptr, vtable) = self.as_mut_ptr();
+        unsafe { (vtable.poll_close)(ptr, cx) }
+    }
+}
+
+impl<'a, Item, E, const N: usize> Drop for StackSinkImpl<'a, Item, E, N> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.vtable.drop)(self.buffer.buffer.as_mut_ptr().cast());
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! A stack-allocated stream with a fixed-size, aligned buffer.
+//!
+//! This is the [`Stream`] counterpart of [`StackFuture`](crate::StackFuture).
+//! Creating a [`StackStream`] will fail if the stream is too large or has
+//! too big alignment requirements.
+use core::{
+    fmt,
+    future::poll_fn,
+    marker::{PhantomData, PhantomPinned},
+    mem::{align_of, size_of},
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "async_iterator")]
+use core::async_iter::AsyncIterator;
+
+use futures_core::Stream;
+
+use crate::{AlignedBuffer, CreateError, drop_shim};
+
+struct StreamVTable<T> {
+    poll_next: unsafe fn(*mut u8, cx: &mut Context<'_>) -> Poll<Option<T>>,
+    drop: unsafe fn(*mut u8),
+}
+
+// Manual impls, since deriving would require `T: Copy`.
+impl<T> Clone for StreamVTable<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StreamVTable<T> {}
+
+impl<T> StreamVTable<T> {
+    fn new<S: Stream<Item = T>>() -> Self {
+        Self {
+            poll_next: poll_next_shim::<S>,
+            drop: drop_shim::<S>,
+        }
+    }
+}
+
+/// Polls the erased stream of type `S` at `ptr` for its next item.
+///
+/// Named and `#[inline]` like the future vtable shims, for readable backtraces.
+#[inline]
+unsafe fn poll_next_shim<S: Stream>(ptr: *mut u8, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+    unsafe { Pin::new_unchecked(&mut *(ptr as *mut S)) }.poll_next(cx)
+}
+
+/// A stack-allocated stream that erases the concrete type of the stream.
+///
+/// This is the non-Send version of the stream.
+#[repr(transparent)]
+pub struct LocalStackStream<'a, T, const N: usize = 64>(
+    StackStreamImpl<'a, T, N>,
+    PhantomData<*const ()>,
+);
+
+impl<'a, T, const N: usize> fmt::Debug for LocalStackStream<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalStackStream")
+            .field("size", &size_of::<Self>())
+            .field("alignment", &align_of::<Self>())
+            .finish()
+    }
+}
+
+impl<'a, T, const N: usize> LocalStackStream<'a, T, N> {
+    /// Creates a new stack stream from a concrete stream.
+    ///
+    /// Returns an error if the stream is too large or has incompatible alignment.
+    pub fn new<S: Stream<Item = T> + 'a>(stream: S) -> Result<Self, CreateError> {
+        Ok(Self(StackStreamImpl::new(stream)?, PhantomData))
+    }
+
+    /// Creates a new stack stream from an async iterator, e.g. an `async gen` block.
+    #[cfg(feature = "async_iterator")]
+    pub fn from_async_iter<I: AsyncIterator<Item = T> + 'a>(iter: I) -> Result<Self, CreateError> {
+        Self::new(AsyncIter(iter))
+    }
+
+    /// Returns a future that resolves to the next item of the stream.
+    ///
+    /// Like `StreamExt::next`, but without depending on `futures-util`. It is
+    /// named differently so that it does not shadow `StreamExt::next`. The
+    /// stream must be pinned, e.g. with [`pin!`](core::pin::pin).
+    pub fn next_item(mut self: Pin<&mut Self>) -> impl Future<Output = Option<T>> {
+        poll_fn(move |cx| self.as_mut().poll_next(cx))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackStreamImpl<'a, T, N>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackStreamImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+impl<'a, T, const N: usize> Stream for LocalStackStream<'a, T, N> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner().poll_next(cx)
+    }
+}
+
+/// A stack-allocated stream that erases the concrete type of the stream.
+///
+/// This is the Send version of the stream.
+#[repr(transparent)]
+pub struct StackStream<'a, T, const N: usize = 64>(StackStreamImpl<'a, T, N>);
+
+impl<'a, T, const N: usize> fmt::Debug for StackStream<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackStream")
+            .field("size", &size_of::<Self>())
+            .field("alignment", &align_of::<Self>())
+            .finish()
+    }
+}
+
+impl<'a, T, const N: usize> StackStream<'a, T, N> {
+    /// Creates a new stack stream from a concrete stream.
+    ///
+    /// Returns an error if the stream is too large or has incompatible alignment.
+    pub fn new<S: Stream<Item = T> + Send + 'a>(stream: S) -> Result<Self, CreateError> {
+        Ok(Self(StackStreamImpl::new(stream)?))
+    }
+
+    /// Creates a new stack stream from an async iterator, e.g. an `async gen` block.
+    #[cfg(feature = "async_iterator")]
+    pub fn from_async_iter<I: AsyncIterator<Item = T> + Send + 'a>(
+        iter: I,
+    ) -> Result<Self, CreateError> {
+        Self::new(AsyncIter(iter))
+    }
+
+    /// Returns a future that resolves to the next item of the stream.
+    ///
+    /// Like `StreamExt::next`, but without depending on `futures-util`. It is
+    /// named differently so that it does not shadow `StreamExt::next`. The
+    /// stream must be pinned, e.g. with [`pin!`](core::pin::pin).
+    pub fn next_item(mut self: Pin<&mut Self>) -> impl Future<Output = Option<T>> {
+        poll_fn(move |cx| self.as_mut().poll_next(cx))
+    }
+
+    // Safe helper to access inner as pinned.
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut StackStreamImpl<'a, T, N>> {
+        // Safe because #[repr(transparent)] ensures Pin<&mut Self> is equivalent to Pin<&mut StackStreamImpl>.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }
+    }
+}
+
+impl<'a, T, const N: usize> Stream for StackStream<'a, T, N> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner().poll_next(cx)
+    }
+}
+
+/// A stack-allocated stream with a fixed-size, aligned buffer.
+///
+/// Safety: this hides the Send-ness of the inner stream type, so it must not
+/// be publicly accessible outside of this crate.
+struct StackStreamImpl<'a, T, const N: usize> {
+    buffer: AlignedBuffer<N>,
+    vtable: StreamVTable<T>,
+    // Ties the erased stream's lifetime to the stack stream.
+    _lifetime: PhantomData<&'a ()>,
+    _pinned: PhantomPinned,
+}
+
+impl<'a, T, const N: usize> StackStreamImpl<'a, T, N> {
+    fn new<S: Stream<Item = T> + 'a>(stream: S) -> Result<Self, CreateError> {
+        AlignedBuffer::<N>::check::<S>()?;
+
+        let mut buffer = AlignedBuffer::uninit();
+
+        // Move the stream into the buffer.
+        unsafe {
+            ptr::write(buffer.buffer.as_mut_ptr().cast::<S>(), stream);
+        }
+
+        Ok(Self {
+            buffer,
+            vtable: StreamVTable::new::<S>(),
+            _lifetime: PhantomData,
+            _pinned: PhantomPinned,
+        })
+    }
+}
+
+impl<'a, T, const N: usize> Stream for StackStreamImpl<'a, T, N> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (this.vtable.poll_next)(this.buffer.buffer.as_mut_ptr().cast(), cx)
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Drop for StackStreamImpl<'a, T, N> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.vtable.drop)(self.buffer.buffer.as_mut_ptr().cast());
+        }
+    }
+}
+
+/// Adapts an [`AsyncIterator`] to a [`Stream`].
+#[cfg(feature = "async_iterator")]
+struct AsyncIter<I>(I);
+
+#[cfg(feature = "async_iterator")]
+impl<I: AsyncIterator> Stream for AsyncIter<I> {
+    type Item = I::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Safe because the iterator is structurally pinned.
+        unsafe { self.map_unchecked_mut(|s| &mut s.0) }.poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
@@ -0,0 +1,59 @@
+//! Helpers for stack futures whose output is a `Result`.
+//!
+//! With the `futures` feature enabled, these implement `TryFuture` through the
+//! blanket impl in `futures-core`, so they compose with `TryFutureExt`.
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Align, LocalStackFuture, StackFuture, SupportedAlignment};
+
+/// A [`LocalStackFuture`] that resolves to a `Result`.
+pub type LocalStackTryFuture<'a, V, E, const N: usize = 64, const ALIGN: usize = 16> =
+    LocalStackFuture<'a, Result<V, E>, N, ALIGN>;
+
+/// A [`StackFuture`] that resolves to a `Result`.
+pub type StackTryFuture<'a, V, E, const N: usize = 64, const ALIGN: usize = 16> =
+    StackFuture<'a, Result<V, E>, N, ALIGN>;
+
+impl<'a, V, E, const N: usize, const ALIGN: usize> LocalStackFuture<'a, Result<V, E>, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Polls the future, returning the `Result` of the inner future.
+    pub fn try_poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<V, E>> {
+        self.poll(cx)
+    }
+
+    /// Creates a new stack future, or boxes the future if it does not fit.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn ok_or_box<F: Future<Output = Result<V, E>> + 'a>(
+        future: F,
+    ) -> Result<Self, Pin<Box<dyn Future<Output = Result<V, E>> + 'a>>> {
+        Self::try_new(future).map_err(|(_, future)| Box::pin(future) as Pin<Box<_>>)
+    }
+}
+
+impl<'a, V, E, const N: usize, const ALIGN: usize> StackFuture<'a, Result<V, E>, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Polls the future, returning the `Result` of the inner future.
+    pub fn try_poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<V, E>> {
+        self.poll(cx)
+    }
+
+    /// Creates a new stack future, or boxes the future if it does not fit.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn ok_or_box<F: Future<Output = Result<V, E>> + Send + 'a>(
+        future: F,
+    ) -> Result<Self, Pin<Box<dyn Future<Output = Result<V, E>> + Send + 'a>>> {
+        Self::try_new(future).map_err(|(_, future)| Box::pin(future) as Pin<Box<_>>)
+    }
+}
@@ -0,0 +1,156 @@
+//! Stack futures for erasing futures that are `Unpin`.
+//!
+//! [`StackFuture`](crate::StackFuture) is always `!Unpin`, since the erased
+//! future might rely on not being moved after it was first polled. If the erased
+//! future is `Unpin` itself, moving the buffer around is fine, so the wrappers
+//! in this module can be `Unpin` and be polled without pinning them first.
+use core::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    mem::align_of,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Align, AlignedBuffer, CreateError, SupportedAlignment, stack_future::StackFutureImpl};
+
+/// A stack-allocated future that erases the concrete type of an `Unpin` future.
+///
+/// This is the non-Send version of the future.
+#[must_use = "futures do nothing unless awaited"]
+#[repr(transparent)]
+pub struct LocalUnpinStackFuture<'a, T, const N: usize = 64, const ALIGN: usize = 16>(
+    StackFutureImpl<'a, T, N, ALIGN>,
+    PhantomData<*const ()>,
+)
+where
+    Align<ALIGN>: SupportedAlignment;
+
+// Safe because the constructor only accepts `Unpin` futures.
+impl<'a, T, const N: usize, const ALIGN: usize> Unpin for LocalUnpinStackFuture<'a, T, N, ALIGN> where
+    Align<ALIGN>: SupportedAlignment
+{
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> fmt::Debug
+    for LocalUnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_debug("LocalUnpinStackFuture", f)
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> LocalUnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new stack future from a concrete `Unpin` future.
+    ///
+    /// Returns an error if the future is too large or has incompatible alignment.
+    pub fn new_unpin<F: Future<Output = T> + Unpin + 'a>(future: F) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new(future)?, PhantomData))
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        align_of::<AlignedBuffer<N, ALIGN>>()
+    }
+
+    /// Returns how often the inner future has been polled.
+    pub fn poll_count(&self) -> u64 {
+        self.0.poll_count()
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> Future for LocalUnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the inner future is Unpin.
+        unsafe { Pin::new_unchecked(&mut self.get_mut().0) }.poll(cx)
+    }
+}
+
+/// A stack-allocated future that erases the concrete type of an `Unpin` future.
+///
+/// This is the Send version of the future.
+#[must_use = "futures do nothing unless awaited"]
+#[repr(transparent)]
+pub struct UnpinStackFuture<'a, T, const N: usize = 64, const ALIGN: usize = 16>(
+    StackFutureImpl<'a, T, N, ALIGN>,
+)
+where
+    Align<ALIGN>: SupportedAlignment;
+
+// Safe because the constructor only accepts `Unpin` futures.
+impl<'a, T, const N: usize, const ALIGN: usize> Unpin for UnpinStackFuture<'a, T, N, ALIGN> where
+    Align<ALIGN>: SupportedAlignment
+{
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> fmt::Debug for UnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_debug("UnpinStackFuture", f)
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> UnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    /// Creates a new stack future from a concrete `Unpin` future.
+    ///
+    /// Returns an error if the future is too large or has incompatible alignment.
+    pub fn new_unpin<F: Future<Output = T> + Unpin + Send + 'a>(
+        future: F,
+    ) -> Result<Self, CreateError> {
+        Ok(Self(StackFutureImpl::new(future)?))
+    }
+
+    /// The capacity of the inline buffer in bytes, usable in const contexts.
+    pub const CAPACITY: usize = N;
+
+    /// Returns the capacity of the inline buffer in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the alignment of the inline buffer in bytes.
+    pub const fn alignment() -> usize {
+        align_of::<AlignedBuffer<N, ALIGN>>()
+    }
+
+    /// Returns how often the inner future has been polled.
+    pub fn poll_count(&self) -> u64 {
+        self.0.poll_count()
+    }
+}
+
+impl<'a, T, const N: usize, const ALIGN: usize> Future for UnpinStackFuture<'a, T, N, ALIGN>
+where
+    Align<ALIGN>: SupportedAlignment,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safe because the inner future is Unpin.
+        unsafe { Pin::new_unchecked(&mut self.get_mut().0) }.poll(cx)
+    }
+}
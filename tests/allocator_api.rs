@@ -0,0 +1,45 @@
+#![cfg(feature = "allocator_api")]
+#![feature(allocator_api)]
+use std::{
+    alloc::{AllocError, Allocator, Global, Layout},
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use stack_future::{LocalSmallFuture, SmallFuture};
+
+/// An allocator that keeps track of the number of live allocations.
+struct CountingAllocator<'a>(&'a AtomicUsize);
+
+unsafe impl Allocator for CountingAllocator<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+        unsafe { Global.deallocate(ptr, layout) }
+    }
+}
+
+async fn large_size() -> u64 {
+    let large = [1u8; 1024];
+    tokio::task::yield_now().await;
+    large.iter().map(|&x| x as u64).sum()
+}
+
+#[tokio::test]
+async fn heap_fallback_uses_allocator() {
+    let live = AtomicUsize::new(0);
+    let future = SmallFuture::<_, 16>::new_in(large_size(), CountingAllocator(&live));
+    assert!(future.is_heap());
+    assert_eq!(live.load(Ordering::SeqCst), 1);
+    assert_eq!(future.await, 1024);
+    assert_eq!(live.load(Ordering::SeqCst), 0);
+
+    let future = LocalSmallFuture::<_, 2048>::new_in(large_size(), CountingAllocator(&live));
+    assert!(future.is_inline());
+    assert_eq!(live.load(Ordering::SeqCst), 0);
+    assert_eq!(future.await, 1024);
+}
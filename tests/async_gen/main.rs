@@ -0,0 +1,8 @@
+//! Erasing `async gen` blocks, which requires a nightly compiler.
+//!
+//! The tests live in a separate module, since a stable compiler rejects the
+//! syntax even in code that is configured out.
+#![cfg_attr(feature = "async_iterator", feature(gen_blocks))]
+
+#[cfg(feature = "async_iterator")]
+mod stream;
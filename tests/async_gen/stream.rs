@@ -0,0 +1,24 @@
+use std::pin::pin;
+
+use futures::StreamExt;
+use stack_future::{LocalStackStream, StackStream};
+
+#[tokio::test]
+async fn async_gen() {
+    let stream = StackStream::<_, 128>::from_async_iter(async gen {
+        for i in 0u64..3 {
+            tokio::task::yield_now().await;
+            yield i;
+        }
+    })
+    .unwrap();
+    assert_eq!(stream.collect::<Vec<_>>().await, vec![0, 1, 2]);
+    let mut stream = pin!(
+        LocalStackStream::<_, 128>::from_async_iter(async gen {
+            yield 42u64;
+        })
+        .unwrap()
+    );
+    assert_eq!(stream.as_mut().next_item().await, Some(42));
+    assert_eq!(stream.next_item().await, None);
+}
@@ -0,0 +1,59 @@
+//! Polls stack futures using only `core`, as a bare-metal executor would.
+#![no_std]
+
+use core::{
+    pin::pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use stack_future::{LocalStackFuture, StackFuture};
+
+static WAKES: AtomicUsize = AtomicUsize::new(0);
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |data| RawWaker::new(data, &VTABLE),
+    |_| {
+        WAKES.fetch_add(1, Ordering::SeqCst);
+    },
+    |_| {
+        WAKES.fetch_add(1, Ordering::SeqCst);
+    },
+    |_| {},
+);
+
+/// A future that wakes itself and returns pending once.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if core::mem::replace(&mut self.0, true) {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn poll_with_raw_waker() {
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+
+    let mut future = pin!(
+        StackFuture::<_, 64>::new(async {
+            YieldOnce(false).await;
+            42u32
+        })
+        .unwrap()
+    );
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(42));
+
+    let mut future = pin!(LocalStackFuture::<_, 64>::new(YieldOnce(true)).unwrap());
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(()));
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "alloc")]
+use std::future::poll_fn;
+
+use stack_future::{StackFuture, StackFuturePool};
+
+/// Test that futures complete in order of readiness and slots are reused.
+#[tokio::test]
+async fn poll_any() {
+    let mut pool = StackFuturePool::<u64, 128>::new(2);
+    let slow = pool
+        .push(
+            StackFuture::new(async {
+                for _ in 0..10 {
+                    tokio::task::yield_now().await;
+                }
+                1
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    let fast = pool
+        .push(StackFuture::new(std::future::ready(2)).unwrap())
+        .unwrap();
+    assert!(pool.push(StackFuture::from_ready(3)).is_err());
+    assert_eq!(pool.len(), 2);
+
+    assert_eq!(poll_fn(|cx| pool.poll_any(cx)).await, (fast, 2));
+    assert_eq!(pool.push(StackFuture::from_ready(3)).unwrap(), fast);
+    assert_eq!(poll_fn(|cx| pool.poll_any(cx)).await, (fast, 3));
+    assert_eq!(poll_fn(|cx| pool.poll_any(cx)).await, (slow, 1));
+    assert!(pool.is_empty());
+}
+
+/// Test that an always ready future does not starve the other slots.
+#[tokio::test]
+async fn poll_any_fair() {
+    let mut pool = StackFuturePool::<u64, 128>::new(2);
+    let first = pool.push(StackFuture::from_ready(0)).unwrap();
+    let second = pool
+        .push(
+            StackFuture::new(async {
+                tokio::task::yield_now().await;
+                1
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    let mut completed = false;
+    for _ in 0..10 {
+        let (index, output) = poll_fn(|cx| pool.poll_any(cx)).await;
+        if index == second {
+            assert_eq!(output, 1);
+            completed = true;
+            break;
+        }
+        // Keep the first slot always ready.
+        assert_eq!(index, first);
+        assert_eq!(pool.push(StackFuture::from_ready(0)).unwrap(), first);
+    }
+    assert!(completed);
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "alloc")]
 use std::{pin::Pin, rc::Rc, sync::OnceLock};
 
 use stack_future::{LocalSmallFuture, SmallFuture};
@@ -45,9 +46,10 @@ async fn non_send_future() -> u64 {
 }
 
 // Static assertions for trait implementations.
-assert_not_impl_any!(LocalSmallFuture<'static, u64, 128>: Send, Unpin);
-assert_impl_all!(SmallFuture<'static, u64, 128>: Send);
+assert_not_impl_any!(LocalSmallFuture<'static, u64, 128>: Send, Sync, Unpin);
+assert_impl_all!(SmallFuture<'static, u64, 128>: Send, Sync);
 assert_not_impl_any!(SmallFuture<'static, u64, 128>: Unpin);
+assert_not_impl_any!(LocalSmallFuture<'static, Rc<()>, 128>: Send, Sync);
 
 #[tokio::test]
 async fn smoke_test() {
@@ -72,7 +74,7 @@ static GLOBAL_TASK: OnceLock<SmallFuture<'static, u64, 128>> = OnceLock::new();
 #[tokio::test]
 async fn static_future_test() {
     let future = SmallFuture::<_, 128>::new(simple());
-    // Stores a SmallFutureSend in a 'static context, verifying compatibility with 'static futures.
+    // Stores a SmallFuture in a 'static context, verifying compatibility with 'static futures.
     GLOBAL_TASK.set(future).unwrap();
 }
 
@@ -83,3 +85,138 @@ async fn test_boxing_for_unpin() {
     let boxed: BoxFuture<u64> = Box::pin(future);
     assert_eq!(boxed.await, 42);
 }
+
+#[tokio::test]
+async fn storage_predicates() {
+    let future = SmallFuture::<_, 32>::new(simple());
+    assert!(future.is_inline() && !future.is_heap());
+    assert_eq!(future.await, 42);
+    let future = SmallFuture::<_, 16>::new(large_size());
+    assert!(future.is_heap() && !future.is_inline());
+    assert_eq!(future.await, 42);
+    let future = LocalSmallFuture::<_, 1024>::new(large_align());
+    assert!(future.is_heap());
+    assert_eq!(future.await, 32640);
+}
+
+#[test]
+fn debug_uses_type_names() {
+    let future = LocalSmallFuture::<_, 32>::new(simple());
+    assert!(format!("{future:?}").starts_with("LocalSmallFuture "));
+    let future = SmallFuture::<_, 32>::new(simple());
+    assert!(format!("{future:?}").starts_with("SmallFuture "));
+}
+
+#[test]
+fn stored_size() {
+    let future = LocalSmallFuture::<_, 16>::new(large_size());
+    assert!(future.is_heap());
+    assert!(future.stored_size() > 16);
+    let future = SmallFuture::<_, 1024>::new(large_align());
+    assert!(future.is_heap());
+    assert_eq!(future.stored_align(), 256);
+    let future = SmallFuture::<_, 32>::new(simple());
+    assert!(future.is_inline());
+    assert!(future.stored_size() <= 32);
+}
+
+/// Test that polls of the future are counted, inline or on the heap.
+#[test]
+fn poll_count() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(SmallFuture::<_, 0>::new(std::future::ready(1u8)));
+    assert!(future.is_heap());
+    assert_eq!(future.poll_count(), 0);
+    assert!(future.as_mut().poll(&mut cx).is_ready());
+    assert_eq!(future.poll_count(), 1);
+}
+
+/// Test that futures can be converted with the extension trait.
+#[tokio::test]
+async fn into_small() {
+    use stack_future::StackFutureExt;
+
+    let future: SmallFuture<_, 128> = simple().into_small();
+    assert_eq!(future.await, 42);
+    let future = non_send_future().into_local_small::<16>();
+    assert!(future.is_heap());
+    assert_eq!(future.await, 42);
+}
+
+/// Test that new_heap allocates even if the future would fit inline.
+#[tokio::test]
+async fn new_heap() {
+    let future = SmallFuture::<_, 256>::new_heap(simple());
+    assert!(future.is_heap());
+    assert_eq!(future.await, 42);
+    let future = LocalSmallFuture::<_, 256>::new_heap(non_send_future());
+    assert!(future.is_heap());
+    assert_eq!(future.await, 42);
+}
+
+/// Test that try_new_inline errors instead of falling back to the heap.
+#[tokio::test]
+async fn try_new_inline() {
+    use stack_future::CreateError;
+
+    let future = SmallFuture::<_, 256>::try_new_inline(simple()).unwrap();
+    assert!(future.is_inline());
+    assert_eq!(future.await, 42);
+    assert!(matches!(
+        SmallFuture::<_, 16>::try_new_inline(complex()),
+        Err(CreateError::SizeTooLarge { .. })
+    ));
+    assert!(matches!(
+        LocalSmallFuture::<_, 1024>::try_new_inline(large_align()),
+        Err(CreateError::AlignmentMismatch { .. })
+    ));
+}
+
+/// Test that try_new stores futures like new when allocation succeeds.
+#[tokio::test]
+async fn try_new() {
+    let future = SmallFuture::<_, 256>::try_new(simple())
+        .map_err(|(e, _)| e)
+        .unwrap();
+    assert!(future.is_inline());
+    assert_eq!(future.await, 42);
+    let future = LocalSmallFuture::<_, 16>::try_new(complex())
+        .map_err(|(e, _)| e)
+        .unwrap();
+    assert!(future.is_heap());
+    assert_eq!(future.await, 4950);
+}
+
+/// Test that the layout description reports heap storage.
+#[test]
+fn info() {
+    use stack_future::Storage;
+
+    let future = SmallFuture::<_, 256>::new_heap(simple());
+    assert_eq!(future.info().storage, Storage::Heap);
+    assert_eq!(future.info().capacity, 256);
+    let future = SmallFuture::<_, 256>::new(simple());
+    assert_eq!(future.info().storage, Storage::Inline);
+}
+
+/// Test converting an inline small future into a stack future.
+#[tokio::test]
+async fn into_stack_if_inline() {
+    let future = SmallFuture::<_, 256>::new(complex());
+    let future = future.into_stack_if_inline().unwrap();
+    assert_eq!(future.stored_size(), std::mem::size_of_val(&complex()));
+    assert_eq!(future.await, 4950);
+
+    let future = SmallFuture::<_, 256>::new_heap(complex());
+    let future = future.into_stack_if_inline().unwrap_err();
+    assert!(future.is_heap());
+    assert_eq!(future.await, 4950);
+
+    // The inner future is moved, not dropped.
+    let value = Rc::new(());
+    let guard = value.clone();
+    let future = LocalSmallFuture::<_, 64>::new(async move { Rc::strong_count(&guard) });
+    let future = future.into_stack_if_inline().unwrap();
+    assert_eq!(future.await, 2);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "alloc")]
+use std::rc::Rc;
+
+use stack_future::StackBox;
+use static_assertions::assert_not_impl_any;
+
+assert_not_impl_any!(StackBox<'static, 64>: Send, Sync);
+
+/// Erases a closure, returning a function that calls it through a pointer.
+fn erase<'a, F: FnMut() + 'a>(f: F) -> (StackBox<'a, 16>, unsafe fn(*mut u8)) {
+    (StackBox::new(f), |ptr| unsafe { (*ptr.cast::<F>())() })
+}
+
+#[test]
+fn smoke_test() {
+    let mut count = 0;
+    let (mut closure, call) = erase(|| count += 1);
+    assert!(closure.is_inline());
+    unsafe {
+        call(closure.as_mut_ptr());
+        call(closure.as_mut_ptr());
+    }
+    drop(closure);
+    assert_eq!(count, 2);
+
+    let large = StackBox::<16>::new([1u64; 8]);
+    assert!(large.is_heap());
+    assert_eq!(large.stored_size(), 64);
+    assert_eq!(unsafe { *large.as_ptr().cast::<[u64; 8]>() }, [1; 8]);
+}
+
+/// Test that the stored value is dropped exactly once, inline or on the heap.
+#[test]
+fn drop_value() {
+    let value = Rc::new(());
+    drop(StackBox::<8>::new(value.clone()));
+    drop(StackBox::<0>::new(value.clone()));
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+/// Test that zero-sized values with large alignment can be moved to the heap.
+#[test]
+fn zero_sized_heap() {
+    #[repr(align(64))]
+    struct Aligned;
+
+    let value = StackBox::<0>::new(Aligned);
+    assert!(value.is_heap());
+    assert_eq!(value.as_ptr() as usize % 64, 0);
+}
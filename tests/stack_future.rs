@@ -1,6 +1,9 @@
-use std::sync::OnceLock;
+use std::{pin::Pin, rc::Rc, sync::OnceLock};
 
-use stack_future::{CreateError, LocalStackFuture, StackFuture};
+use stack_future::{
+    CreateError, LocalStackFuture, RawVTable, SendRawVTable, StackFuture, StaticStackFuture,
+    stack_future_assert_fits,
+};
 use static_assertions::{assert_impl_all, assert_not_impl_any};
 
 async fn simple() -> u64 {
@@ -52,15 +55,1283 @@ async fn smoke_test() {
 }
 
 static GLOBAL_TASK: OnceLock<StackFuture<'static, u64, 128>> = OnceLock::new();
+static DEFAULT_TASK: OnceLock<StackFuture<'static, u64>> = OnceLock::new();
+static STATIC_TASK: OnceLock<StaticStackFuture<u64, 128>> = OnceLock::new();
 
 /// Test that the the static lifetime future is properly captured.
 #[tokio::test]
 async fn static_future_test() {
     let future = StackFuture::<_, 128>::new(simple()).unwrap();
-    // This fails to compile with vtable: &'a VTable<T> because StackFuture<'static, i32, 128> is not 'static.
+    // The vtable is stored by value, so StackFuture<'static, u64, 128> is 'static.
     GLOBAL_TASK.set(future).unwrap();
+    let future = StackFuture::new(simple()).unwrap();
+    assert_eq!(future.capacity(), 64);
+    DEFAULT_TASK.set(future).unwrap();
+    let future: StaticStackFuture<u64, 128> = StackFuture::new_static(simple()).unwrap();
+    STATIC_TASK.set(future).unwrap();
 }
 
-assert_not_impl_any!(LocalStackFuture<'static, u64, 128>: Send, Unpin);
+/// Test that a future borrowing from the surrounding scope can be erased.
+#[tokio::test]
+async fn borrowing_future() {
+    let data = vec![1u64, 2, 3];
+    let slice = data.as_slice();
+    let future = StackFuture::<_, 64>::new(async move { slice.iter().sum::<u64>() }).unwrap();
+    assert_eq!(future.await, 6);
+}
+
+stack_future_assert_fits!(std::future::Ready<u64>, 16);
+stack_future_assert_fits!(std::future::Pending<[u64; 4]>, 0);
+
+const _: [u8; StackFuture::<(), 64>::CAPACITY] = [0; 64];
+const _: () = assert!(LocalStackFuture::<(), 32, 16>::CAPACITY == 32);
+
+assert_not_impl_any!(LocalStackFuture<'static, u64, 128>: Send, Sync, Unpin);
 assert_impl_all!(StackFuture<'static, u64, 128>: Send);
 assert_not_impl_any!(StackFuture<'static, u64, 128>: Unpin);
+assert_impl_all!(StackFuture<'static, u64>: Send);
+assert_not_impl_any!(LocalStackFuture<'static, u64>: Send, Unpin);
+
+#[test]
+fn capacity_and_alignment() {
+    let future = StackFuture::<u64, 128>::new(simple()).unwrap();
+    assert_eq!(future.capacity(), 128);
+    assert_eq!(StackFuture::<u64, 128>::alignment(), 16);
+    let future = LocalStackFuture::<u64, 32>::new(simple()).unwrap();
+    assert_eq!(future.capacity(), 32);
+    assert_eq!(LocalStackFuture::<u64, 32>::alignment(), 16);
+}
+
+/// Test that a future that does not fit is handed back and can be boxed instead.
+#[tokio::test]
+async fn try_new_returns_future() {
+    let (err, future) = StackFuture::<_, 16>::try_new(complex()).unwrap_err();
+    assert!(matches!(err, CreateError::SizeTooLarge { .. }));
+    assert_eq!(Box::pin(future).await, 4950);
+    let (err, future) = LocalStackFuture::<_, 1024>::try_new(large_align()).unwrap_err();
+    assert!(matches!(err, CreateError::AlignmentMismatch { .. }));
+    assert_eq!(Box::pin(future).await, 32640);
+}
+
+/// Test that fused futures report termination through the wrapper.
+#[cfg(feature = "futures")]
+#[tokio::test]
+async fn fused_future() {
+    use futures::future::{FusedFuture, FutureExt};
+
+    let mut future = std::pin::pin!(StackFuture::<_, 64>::new_fused(simple().fuse()).unwrap());
+    assert!(!future.is_terminated());
+    assert_eq!((&mut future).await, 42);
+    assert!(future.is_terminated());
+    let future = LocalStackFuture::<_, 64>::new(simple()).unwrap();
+    assert!(!future.is_terminated());
+
+    // Futures that are not fused are terminated once they completed.
+    let mut future = std::pin::pin!(StackFuture::<_, 64>::new(async {}).unwrap());
+    assert!(!future.is_terminated());
+    (&mut future).await;
+    assert!(future.is_terminated());
+}
+
+/// Test that boxing produces an Unpin future that keeps the Send bound.
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn boxed() {
+    fn assert_send_unpin<F: Future + Send + Unpin>(f: F) -> F {
+        f
+    }
+    let future = assert_send_unpin(StackFuture::<_, 128>::new(simple()).unwrap().boxed());
+    assert_eq!(future.await, 42);
+    let future = LocalStackFuture::<_, 128>::new(simple())
+        .unwrap()
+        .boxed_local();
+    assert_eq!(future.await, 42);
+}
+
+/// Test that a larger buffer alignment accepts over-aligned futures.
+#[tokio::test]
+async fn configurable_alignment() {
+    assert_eq!(StackFuture::<u64, 1024, 256>::alignment(), 256);
+    let future = LocalStackFuture::<_, 1024, 256>::new(large_align()).unwrap();
+    assert_eq!(future.await, 32640);
+    let res = StackFuture::<_, 1024, 16>::new(large_align());
+    assert!(matches!(
+        res,
+        Err(CreateError::AlignmentMismatch {
+            alignment: 256,
+            expected: 16,
+            ..
+        })
+    ));
+}
+
+/// Test that take_output never polls the inner future past completion.
+#[test]
+fn take_output_past_completion() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackFuture::<_, 32>::new(simple()).unwrap());
+    assert_eq!(
+        future.as_mut().take_output(&mut cx),
+        std::task::Poll::Ready(Some(42))
+    );
+    assert_eq!(
+        future.as_mut().take_output(&mut cx),
+        std::task::Poll::Ready(None)
+    );
+}
+
+/// Test driving futures by value from a vec that is never reallocated.
+#[test]
+fn poll_unpin_in_place() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut futures = Vec::with_capacity(3);
+    for _ in 0..3 {
+        futures.push(StackFuture::<_, 32>::new(simple()).unwrap());
+    }
+    for future in &mut futures {
+        // Safe because the vec is not modified until the futures are dropped.
+        let res = unsafe { future.poll_unpin(&mut cx) };
+        assert_eq!(res, std::task::Poll::Ready(42));
+    }
+}
+
+/// Test that mapping the output keeps the future on the stack.
+#[tokio::test]
+async fn map() {
+    let offset = 1u64;
+    let future: StackFuture<_, 128> = StackFuture::<_, 32>::new(simple())
+        .unwrap()
+        .map(move |x| x + offset)
+        .unwrap();
+    assert_eq!(future.await, 43);
+    let future: LocalStackFuture<_, 128> = LocalStackFuture::<_, 32>::new(simple())
+        .unwrap()
+        .map(|x| x.to_string())
+        .unwrap();
+    assert_eq!(future.await, "42");
+    let res: Result<StackFuture<u64, 32>, _> =
+        StackFuture::<_, 32>::new(simple()).unwrap().map(|x| x);
+    assert!(matches!(res, Err(CreateError::SizeTooLarge { .. })));
+}
+
+/// Test that the timeout races the future against a timer on the stack.
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn with_timeout() {
+    use std::time::Duration;
+
+    let future: StackFuture<_, 256> = StackFuture::<_, 32>::new(simple())
+        .unwrap()
+        .with_timeout(Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(future.await.unwrap(), 42);
+    let future: LocalStackFuture<_, 256> =
+        LocalStackFuture::<_, 32>::new(std::future::pending::<u64>())
+            .unwrap()
+            .with_timeout(Duration::from_millis(1))
+            .unwrap();
+    assert!(future.await.is_err());
+}
+
+/// Test that the buffer can be reused for a new future after completion.
+#[tokio::test]
+async fn rearm() {
+    let mut future = std::pin::pin!(StackFuture::<_, 256>::new(simple()).unwrap());
+    assert_eq!(future.as_mut().await, 42);
+    future.as_mut().rearm(complex()).unwrap();
+    assert_eq!(future.as_mut().await, 4950);
+    let res = future.as_mut().rearm(large_align());
+    assert!(matches!(res, Err(CreateError::SizeTooLarge { .. })));
+    future.as_mut().rearm(simple()).unwrap();
+    assert_eq!(future.await, 42);
+}
+
+/// Test that a completed future is replaced by the next one in the same buffer.
+#[test]
+fn poll_replace() {
+    use std::{
+        future::ready,
+        panic::{AssertUnwindSafe, catch_unwind},
+        task::Poll,
+    };
+
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackFuture::<_, 8>::new(ready(1u32)).unwrap());
+    let res = future.as_mut().poll_replace(&mut cx, || ready(2));
+    assert_eq!(res, Poll::Ready(1));
+    let too_large = || async {
+        let x = [3u32; 4];
+        ready(()).await;
+        x[0]
+    };
+    let res = future.as_mut().poll_replace(&mut cx, too_large);
+    assert_eq!(res, Poll::Ready(2));
+    let res = catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx)));
+    assert!(res.is_err());
+}
+
+/// Test that a future that panics during poll is dropped exactly once.
+#[test]
+fn panic_in_poll() {
+    use std::{
+        panic::{AssertUnwindSafe, catch_unwind},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let guard = Guard;
+    let future = StackFuture::<(), 64>::new(async move {
+        let _guard = guard;
+        panic!("boom");
+    })
+    .unwrap();
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = Box::pin(future);
+    let res = catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx)));
+    assert!(res.is_err());
+    // The guard was dropped while unwinding out of the async block.
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+    drop(future);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn stored_size() {
+    let future = StackFuture::<_, 256>::new(complex()).unwrap();
+    assert!(future.stored_size() > 0 && future.stored_size() <= 256);
+    assert_eq!(future.stored_align(), 8);
+    let future = LocalStackFuture::<_, 32>::new(std::future::ready(1u8)).unwrap();
+    assert_eq!(future.stored_size(), 2);
+    assert_eq!(future.stored_align(), 1);
+}
+
+/// Test the helpers for futures that resolve to a `Result`.
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn try_future() {
+    use stack_future::{LocalStackTryFuture, StackTryFuture};
+
+    async fn fallible(ok: bool) -> Result<u64, String> {
+        if ok { Ok(42) } else { Err("failed".into()) }
+    }
+
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackTryFuture::<_, _, 64>::new(fallible(true)).unwrap());
+    assert_eq!(
+        future.as_mut().try_poll(&mut cx),
+        std::task::Poll::Ready(Ok(42))
+    );
+    let Ok(future) = LocalStackTryFuture::<_, _, 64>::ok_or_box(fallible(false)) else {
+        panic!("Expected the future to fit");
+    };
+    assert_eq!(future.await, Err("failed".to_string()));
+    let boxed = StackTryFuture::<_, _, 1>::ok_or_box(fallible(true)).unwrap_err();
+    assert_eq!(boxed.await, Ok(42));
+}
+
+/// Test that stack futures with a `Result` output compose with `TryFutureExt`.
+#[cfg(feature = "futures")]
+#[tokio::test]
+async fn try_future_ext() {
+    use futures::TryFutureExt;
+
+    let future = StackFuture::<_, 64>::new(async { Ok::<_, ()>(41u64) }).unwrap();
+    assert_eq!(future.map_ok(|x| x + 1).await, Ok(42));
+}
+
+/// A zero-sized future that completes immediately.
+struct Noop;
+
+impl Future for Noop {
+    type Output = ();
+
+    fn poll(self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+        std::task::Poll::Ready(())
+    }
+}
+
+static NOOP_TASKS: [StackFuture<'static, (), 0>; 2] =
+    [StackFuture::new_zst(Noop), StackFuture::new_zst(Noop)];
+
+/// Test that zero-sized futures can be erased in a const context.
+#[test]
+fn const_zst_future() {
+    const LOCAL: LocalStackFuture<'static, (), 8> = LocalStackFuture::new_zst(Noop);
+    assert_eq!(NOOP_TASKS[0].stored_size(), 0);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(LOCAL);
+    assert_eq!(future.as_mut().poll(&mut cx), std::task::Poll::Ready(()));
+}
+
+/// Test the shortcuts for ready and pending futures.
+#[tokio::test]
+async fn ready_and_pending() {
+    assert_eq!(StackFuture::<_, 16>::from_ready(42u64).await, 42);
+    assert_eq!(LocalStackFuture::<_, 16>::from_ready(42u64).await, 42);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackFuture::<u64, 0>::from_pending());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+}
+
+/// Test racing two stack futures, and that the loser is dropped.
+#[tokio::test]
+async fn select2() {
+    use stack_future::{Either, select2, select2_local};
+
+    let a = StackFuture::<_, 256>::new(complex()).unwrap();
+    let b = StackFuture::<_, 256>::new(simple()).unwrap();
+    let future: StackFuture<_, 1024> = select2(a, b).unwrap();
+    assert_eq!(future.await, Either::Right(42));
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let a = LocalStackFuture::<_, 256>::new(async move {
+        let _tx = tx;
+        std::future::pending::<u64>().await
+    })
+    .unwrap();
+    let b = LocalStackFuture::<_, 256>::new(simple()).unwrap();
+    let mut future = std::pin::pin!(select2_local::<_, 256, 1024, 16>(a, b).unwrap());
+    assert_eq!(future.as_mut().await, Either::Right(42));
+    // The pending future was dropped as soon as the other one completed.
+    assert!(rx.await.is_err());
+}
+
+#[test]
+fn debug_shows_capacity_and_stored_size() {
+    let future = LocalStackFuture::<_, 32>::new(std::future::ready(1u8)).unwrap();
+    let debug = format!("{future:?}");
+    assert!(debug.contains("capacity: 32"), "{debug}");
+    assert!(debug.contains("stored_size: 2"), "{debug}");
+}
+
+#[test]
+fn debug_uses_type_names() {
+    let future = StackFuture::<_, 32>::new(simple()).unwrap();
+    assert!(format!("{future:?}").starts_with("StackFuture "));
+    let future = LocalStackFuture::<_, 32>::new(simple()).unwrap();
+    assert!(format!("{future:?}").starts_with("LocalStackFuture "));
+}
+
+#[tokio::test]
+async fn local_into_send() {
+    let future = StackFuture::<_, 256>::new(simple()).unwrap();
+    let local = LocalStackFuture::from(future);
+    let future = local.try_into_send().unwrap();
+    assert_eq!(future.await, 42);
+
+    let local = LocalStackFuture::<_, 256>::new(simple()).unwrap();
+    assert!(local.try_into_send().is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn new_or_box() {
+    use stack_future::Either;
+
+    let Either::Left(future) = StackFuture::<_, 256>::new_or_box(simple()) else {
+        panic!("Expected the future to fit");
+    };
+    assert_eq!(future.await, 42);
+    let Either::Right(boxed) = StackFuture::<_, 1>::new_or_box(std::future::ready(42)) else {
+        panic!("Expected the future to be boxed");
+    };
+    assert_eq!(boxed.await, 42);
+}
+
+/// Test that the stored future can be recovered if its type is known.
+#[test]
+fn downcast_ref() {
+    use std::future::{Pending, Ready};
+
+    let future = StackFuture::<_, 16>::new(std::future::ready(42u64)).unwrap();
+    assert!(unsafe { future.downcast_ref::<Ready<u64>>() }.is_some());
+    assert!(unsafe { future.downcast_ref::<Pending<u64>>() }.is_none());
+    assert!(unsafe { future.downcast_ref::<Ready<u32>>() }.is_none());
+}
+
+/// Test that the macro picks the first size that fits, or boxes the future.
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn stack_future_macro() {
+    use stack_future::{Either, stack_future};
+
+    let future = stack_future![complex(); 1, 256, 1024];
+    assert!(matches!(future, Either::Right(Either::Left(_))));
+    assert_eq!(future.await, 4950);
+    let future = stack_future![complex(); 1, 2];
+    assert!(matches!(future, Either::Right(Either::Right(_))));
+    assert_eq!(future.await, 4950);
+}
+
+/// Test that the overflow policy is applied to futures that do not fit.
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn with_overflow() {
+    use stack_future::Overflow;
+
+    let res = StackFuture::<_, 1>::with_overflow(complex(), Overflow::Error);
+    assert!(matches!(res, Err(CreateError::SizeTooLarge { .. })));
+    let res = std::panic::catch_unwind(|| {
+        LocalStackFuture::<_, 1>::with_overflow(complex(), Overflow::Panic)
+    });
+    assert!(res.is_err());
+    let future = StackFuture::<_, 8>::with_overflow(complex(), Overflow::Box).unwrap();
+    assert_eq!(future.stored_size(), 8);
+    assert_eq!(future.await, 4950);
+}
+
+/// Test that the inner future is dropped exactly once, whether or not it completed.
+#[test]
+fn drop_exactly_once() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll},
+    };
+
+    struct Counted {
+        drops: Arc<AtomicUsize>,
+        pending: bool,
+    }
+
+    impl Future for Counted {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if std::mem::take(&mut self.pending) {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut cx = Context::from_waker(std::task::Waker::noop());
+    for polls in 0..3 {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let counted = Counted {
+            drops: drops.clone(),
+            pending: true,
+        };
+        let mut future = Box::pin(StackFuture::<_, 32>::new(counted).unwrap());
+        for _ in 0..polls {
+            let _ = future.as_mut().poll(&mut cx);
+        }
+        drop(future);
+        assert_eq!(drops.load(Ordering::SeqCst), 1, "after {polls} polls");
+    }
+}
+
+/// Test that a stack future can be driven to completion without a runtime.
+#[cfg(feature = "std")]
+#[test]
+fn block_on() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let future = StackFuture::<_, 64>::new(async move { rx.await.unwrap() }).unwrap();
+    let sender = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        tx.send(42u64).unwrap();
+    });
+    assert_eq!(stack_future::block_on(future), 42);
+    sender.join().unwrap();
+}
+
+/// Test that values implementing `IntoFuture` can be erased.
+#[tokio::test]
+async fn from_into_future() {
+    struct Answer;
+
+    impl std::future::IntoFuture for Answer {
+        type Output = u64;
+        type IntoFuture = std::future::Ready<u64>;
+
+        fn into_future(self) -> Self::IntoFuture {
+            std::future::ready(42)
+        }
+    }
+
+    // The size of the produced future is checked, not the size of `Answer`.
+    let res = StackFuture::<_, 8>::from_into_future(Answer);
+    assert!(matches!(
+        res,
+        Err(CreateError::SizeTooLarge { size: 16, .. })
+    ));
+    let future = StackFuture::<_, 16>::from_into_future(Answer).unwrap();
+    assert_eq!(future.await, 42);
+}
+
+/// Test that a future can be placed in caller-provided storage.
+#[tokio::test]
+async fn new_in_storage() {
+    use std::mem::MaybeUninit;
+
+    #[repr(align(16))]
+    struct Arena([MaybeUninit<u8>; 1024]);
+
+    let mut arena = Arena([MaybeUninit::uninit(); 1024]);
+    let future = unsafe { StackFuture::<_, 8>::new_in(complex(), &mut arena.0) }.unwrap();
+    assert_eq!(future.await, 4950);
+    assert!(matches!(
+        unsafe { LocalStackFuture::<_, 8>::new_in(complex(), &mut arena.0[..1]) },
+        Err(CreateError::SizeTooLarge { max_size: 1, .. })
+    ));
+    assert!(matches!(
+        unsafe { LocalStackFuture::<_, 8>::new_in(complex(), &mut arena.0[1..]) },
+        Err(CreateError::AlignmentMismatch { expected: 1, .. })
+    ));
+}
+
+/// Test that a pinned stack future can be embedded and polled without `unsafe`.
+#[tokio::test]
+async fn project_poll() {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    struct Wrapper<'a, 'b> {
+        inner: Pin<&'b mut StackFuture<'a, u64, 256>>,
+    }
+
+    impl Future for Wrapper<'_, '_> {
+        type Output = u64;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+            self.get_mut()
+                .inner
+                .as_mut()
+                .project_poll(cx)
+                .map(|x| x + 1)
+        }
+    }
+
+    let future = std::pin::pin!(StackFuture::<_, 256>::new(simple()).unwrap());
+    assert_eq!(Wrapper { inner: future }.await, 43);
+}
+
+/// Test that futures can be created without a runtime size check.
+#[tokio::test]
+async fn new_exact() {
+    let future = StackFuture::<_, 256>::new_exact(simple());
+    assert_eq!(future.await, 42);
+    let future = LocalStackFuture::<_, 256>::new_exact(complex());
+    assert_eq!(future.await, 4950);
+}
+
+/// Test that polls of the inner future are counted.
+#[test]
+fn poll_count() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let (tx, rx) = tokio::sync::oneshot::channel::<u64>();
+    let mut future = std::pin::pin!(StackFuture::<_, 64>::new(rx).unwrap());
+    assert_eq!(future.poll_count(), 0);
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    tx.send(42).unwrap();
+    assert!(future.as_mut().poll(&mut cx).is_ready());
+    assert_eq!(future.poll_count(), 3);
+    future.as_mut().rearm(std::future::pending()).unwrap();
+    assert_eq!(future.poll_count(), 0);
+}
+
+/// Test that the extension trait preserves the creation errors.
+#[tokio::test]
+async fn try_into_stack() {
+    use stack_future::StackFutureExt;
+
+    let future: StackFuture<_, 256> = simple().try_into_stack().unwrap();
+    assert_eq!(future.await, 42);
+    let res = std::future::ready(1u64).try_into_local_stack::<8, 8>();
+    assert!(matches!(
+        res,
+        Err(CreateError::SizeTooLarge {
+            size: 16,
+            max_size: 8,
+            ..
+        })
+    ));
+    let res = large_align().try_into_stack::<1024, 8>();
+    assert!(matches!(
+        res,
+        Err(CreateError::AlignmentMismatch {
+            alignment: 256,
+            expected: 8,
+            ..
+        })
+    ));
+}
+
+/// Test that the output can be written into a caller-provided slot.
+#[test]
+fn poll_into() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackFuture::<_, 4096>::new(async { [7u8; 1024] }).unwrap());
+    let mut out = None;
+    assert!(future.as_mut().poll_into(&mut cx, &mut out).is_ready());
+    assert_eq!(out, Some([7u8; 1024]));
+}
+
+/// Test that a panic in the inner future is turned into an error output.
+#[cfg(feature = "std")]
+#[tokio::test]
+async fn catch_unwind() {
+    let future = StackFuture::<_, 256>::new(simple()).unwrap();
+    assert_eq!(future.catch_unwind::<512>().unwrap().await.unwrap(), 42);
+
+    let future = StackFuture::<u64, 256>::new(async { panic!("boom") }).unwrap();
+    let payload = future.catch_unwind::<512>().unwrap().await.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+}
+
+/// Test that creation errors name the offending future type.
+#[test]
+fn error_type_name() {
+    let err = StackFuture::<_, 8>::new(std::future::ready(1u64)).unwrap_err();
+    let CreateError::SizeTooLarge { type_name, .. } = err else {
+        panic!("Expected a size error");
+    };
+    assert!(type_name.ends_with("Ready<u64>"), "{type_name}");
+    assert_eq!(
+        err.to_string(),
+        format!("Future `{type_name}` size 16 exceeds capacity 8")
+    );
+}
+
+/// Test that a boxed future can be stored without erasing it again.
+#[cfg(feature = "alloc")]
+#[tokio::test]
+async fn from_boxed() {
+    let future = StackFuture::<_, 16>::from_boxed(Box::pin(complex()));
+    assert_eq!(future.stored_size(), 2 * size_of::<usize>());
+    assert_eq!(future.await, 4950);
+    let future = LocalStackFuture::<_, 16>::from_boxed(Box::pin(simple()));
+    assert_eq!(future.await, 42);
+}
+
+/// Test that zero-sized futures work with a zero-length buffer.
+#[tokio::test]
+async fn zero_length_buffer() {
+    let future = StackFuture::<_, 0>::new(Noop).unwrap();
+    assert_eq!(future.stored_size(), 0);
+    future.await;
+    let future = LocalStackFuture::<(), 0, 4096>::new(std::future::pending()).unwrap();
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert!(std::pin::pin!(future).poll(&mut cx).is_pending());
+    // An async block stores its state, so it is not zero-sized.
+    let res = StackFuture::<_, 0>::new(async {});
+    assert!(matches!(
+        res,
+        Err(CreateError::SizeTooLarge { size: 1, .. })
+    ));
+}
+
+/// Test that a stack future can be moved into a smaller buffer.
+#[tokio::test]
+async fn downsize() {
+    let future = StackFuture::<_, 256>::new(std::future::ready(42u64)).unwrap();
+    let future = future.downsize::<16>().unwrap();
+    assert_eq!(future.capacity(), 16);
+    assert_eq!(future.await, 42);
+    let future = LocalStackFuture::<_, 256>::new(complex()).unwrap();
+    let future = future.downsize::<8>().unwrap_err();
+    assert_eq!(future.await, 4950);
+}
+
+/// Test joining two stack futures, and that pending ones are dropped with the join.
+#[tokio::test]
+async fn join2() {
+    use stack_future::{join2, join2_local};
+
+    let a = StackFuture::<_, 256>::new(complex()).unwrap();
+    let b = StackFuture::<_, 256>::new(async {
+        tokio::task::yield_now().await;
+        "done"
+    })
+    .unwrap();
+    let future: StackFuture<_, 1024> = join2(a, b).unwrap();
+    assert_eq!(future.await, (4950, "done"));
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let a = LocalStackFuture::<_, 256>::new(async move {
+        let _tx = tx;
+        std::future::pending::<u64>().await
+    })
+    .unwrap();
+    let b = LocalStackFuture::<_, 256>::new(simple()).unwrap();
+    let mut future = Box::pin(join2_local::<_, _, 256, 1024, 16>(a, b).unwrap());
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    drop(future);
+    assert!(rx.await.is_err());
+}
+
+/// Test that futures holding 16-byte aligned values fit with the default alignment.
+#[tokio::test]
+async fn default_align_16() {
+    #[repr(align(16))]
+    struct Simd([u32; 4]);
+
+    let future = StackFuture::<_, 128>::new(async {
+        let simd = Simd([1, 2, 3, 4]);
+        tokio::task::yield_now().await;
+        simd.0.iter().sum::<u32>()
+    })
+    .unwrap();
+    assert_eq!(future.stored_align(), 16);
+    assert_eq!(future.await, 10);
+    let res = StackFuture::<_, 1024>::new(large_align());
+    assert!(matches!(
+        res,
+        Err(CreateError::AlignmentMismatch { expected: 16, .. })
+    ));
+}
+
+/// Test the size introspection helpers.
+#[test]
+fn future_size() {
+    use stack_future::{fits, future_size};
+
+    type Ready = std::future::Ready<u64>;
+    assert_eq!(stack_future::future_size::<Ready>(), 16);
+    assert!(fits::<Ready, 16>());
+    assert!(!fits::<Ready, 8>());
+
+    let size = future_size!(complex());
+    assert!(size > future_size!(simple()));
+    assert!(StackFuture::<_, 256>::new(complex()).unwrap().stored_size() == size);
+}
+
+/// Test cloning a stack future before it is polled.
+#[tokio::test]
+async fn clone() {
+    let request = String::from("request");
+    let future = StackFuture::<_, 128>::new_cloneable(std::future::ready(request)).unwrap();
+    let retry = future.clone();
+    assert_eq!(future.await, "request");
+    assert_eq!(retry.await, "request");
+
+    let future = LocalStackFuture::<_, 128>::new(std::future::ready(1u8)).unwrap();
+    assert!(future.try_clone().is_none());
+}
+
+/// Test viewing a stack future as a pinned trait object.
+#[tokio::test]
+async fn as_dyn() {
+    async fn run(future: Pin<&mut (dyn Future<Output = u64> + Send + '_)>) -> u64 {
+        future.await
+    }
+
+    let mut future = std::pin::pin!(StackFuture::<_, 256>::new(complex()).unwrap());
+    assert_eq!(run(future.as_mut().as_dyn()).await, 4950);
+    let mut future = std::pin::pin!(LocalStackFuture::<_, 64>::new(simple()).unwrap());
+    let future: Pin<&mut dyn Future<Output = u64>> = future.as_mut().as_dyn();
+    assert_eq!(future.await, 42);
+}
+
+/// Test dropping a future that holds a waker to its own task and wakes it on drop.
+#[test]
+fn self_waking_drop() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Default)]
+    struct SelfWaking(Option<Waker>);
+
+    impl Future for SelfWaking {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.0 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    impl Drop for SelfWaking {
+        fn drop(&mut self) {
+            if let Some(waker) = self.0.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(StackFuture::<_, 64>::new(SelfWaking::default()).unwrap());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    drop(future);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert_eq!(Arc::strong_count(&counter), 2);
+}
+
+/// Test that mapping the error keeps the future on the stack.
+#[tokio::test]
+async fn map_err() {
+    let future = StackFuture::<_, 32>::new(std::future::ready(Err::<u64, _>(7u8))).unwrap();
+    let future: StackFuture<_, 128> = future.map_err(|e| e.to_string()).unwrap();
+    assert_eq!(future.await, Err("7".to_string()));
+    let future = LocalStackFuture::<_, 32>::new(std::future::ready(Ok::<_, u8>(1u64))).unwrap();
+    let future: LocalStackFuture<_, 128> = future.map_err(u16::from).unwrap();
+    assert_eq!(future.await, Ok(1));
+}
+
+/// Test that a buffer size that is not a multiple of the alignment is fully usable.
+#[test]
+fn unaligned_capacity() {
+    #[repr(align(8))]
+    struct Padded([u8; 24]);
+
+    impl Future for Padded {
+        type Output = u8;
+
+        fn poll(self: Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<u8> {
+            std::task::Poll::Ready(self.0[23])
+        }
+    }
+
+    let future = StackFuture::<_, 24>::new(Padded([1; 24])).unwrap();
+    assert_eq!(future.stored_size(), 24);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert_eq!(
+        std::pin::pin!(future).poll(&mut cx),
+        std::task::Poll::Ready(1)
+    );
+    // The stack future is padded to a multiple of the alignment.
+    assert!(std::mem::size_of::<StackFuture<u8, 24>>().is_multiple_of(16));
+}
+
+/// Test that the storage overhead accounts for the size of a nested stack future.
+#[tokio::test]
+async fn storage_overhead() {
+    type Inner = StackFuture<'static, u64, 32>;
+    let overhead = Inner::storage_overhead();
+    assert_eq!(std::mem::size_of::<Inner>(), 32 + overhead);
+    assert_eq!(LocalStackFuture::<u64, 32>::storage_overhead(), overhead);
+    // The vtable is shared, so it does not add to every stack future.
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(overhead, 32);
+    let inner = Inner::new(simple()).unwrap();
+    let outer = StackFuture::<_, 256>::new(inner).unwrap();
+    assert_eq!(outer.stored_size(), 32 + overhead);
+    assert_eq!(outer.await, 42);
+}
+
+/// Test awaiting a stack future produced by another stack future.
+#[tokio::test]
+#[allow(clippy::async_yields_async)]
+async fn flatten() {
+    let outer = StackFuture::<_, 64>::new(async {
+        tokio::task::yield_now().await;
+        StackFuture::<_, 256>::new(complex()).unwrap()
+    })
+    .unwrap();
+    let future: StackFuture<_, 512> = outer.flatten().unwrap();
+    assert_eq!(future.await, 4950);
+
+    let outer = LocalStackFuture::<_, 64>::new(async { simple() }).unwrap();
+    let res = outer.flatten::<16>();
+    assert!(matches!(res, Err(CreateError::SizeTooLarge { .. })));
+}
+
+/// Test creating a stack future after checking that it fits at compile time.
+#[tokio::test]
+async fn new_unchecked() {
+    stack_future_assert_fits!(std::future::Ready<u64>, 16);
+    let future = unsafe { StackFuture::<_, 16>::new_unchecked(std::future::ready(42u64)) };
+    assert_eq!(future.await, 42);
+    let future = unsafe { LocalStackFuture::<_, 16>::new_unchecked(std::future::ready(42u64)) };
+    assert_eq!(future.await, 42);
+}
+
+/// Test that a cache-line-aligned future fits with a 64 byte buffer alignment.
+#[tokio::test]
+async fn cache_line_alignment() {
+    #[repr(align(64))]
+    struct CacheLine(u64);
+
+    let future = StackFuture::<_, 256, 64>::new(async {
+        let state = CacheLine(42);
+        tokio::task::yield_now().await;
+        state.0
+    })
+    .unwrap();
+    assert_eq!(future.stored_align(), 64);
+    assert_eq!(future.await, 42);
+}
+
+/// Test polling a pinned array of stack futures in place.
+#[test]
+fn poll_all() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut futures = std::pin::pin!([
+        StackFuture::<_, 128>::new(async { 1u64 }).unwrap(),
+        StackFuture::<_, 128>::new(async {
+            tokio::task::yield_now().await;
+            2
+        })
+        .unwrap(),
+    ]);
+    assert_eq!(
+        stack_future::poll_all(futures.as_mut(), &mut cx),
+        [Some(1), None]
+    );
+    // The completed future is not polled again.
+    assert_eq!(
+        stack_future::poll_all(futures.as_mut(), &mut cx),
+        [None, Some(2)]
+    );
+    assert_eq!(
+        stack_future::poll_all(futures.as_mut(), &mut cx),
+        [None, None]
+    );
+}
+
+/// Test converting creation errors to io errors.
+#[cfg(feature = "std")]
+#[test]
+fn io_error() {
+    fn create() -> std::io::Result<StackFuture<'static, u64, 8>> {
+        Ok(StackFuture::new(complex())?)
+    }
+
+    let err = create().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::OutOfMemory);
+    let inner = err.into_inner().unwrap().downcast::<CreateError>().unwrap();
+    assert!(matches!(*inner, CreateError::SizeTooLarge { .. }));
+}
+
+/// Test that now_or_never returns ready outputs and drops pending futures.
+#[tokio::test]
+async fn now_or_never() {
+    assert_eq!(
+        StackFuture::<_, 64>::new(simple()).unwrap().now_or_never(),
+        Some(42)
+    );
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let future = LocalStackFuture::<_, 64>::new(async move {
+        let _tx = tx;
+        std::future::pending::<u64>().await
+    })
+    .unwrap();
+    assert_eq!(future.now_or_never(), None);
+    assert!(rx.await.is_err());
+}
+
+/// Test that a completed stack future reports completion and is not polled again.
+#[test]
+fn is_completed() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future = std::pin::pin!(StackFuture::<_, 64>::new(simple()).unwrap());
+    assert!(!future.is_completed());
+    assert_eq!(future.as_mut().poll(&mut cx), std::task::Poll::Ready(42));
+    assert!(future.is_completed());
+    // Polling the async fn again would panic.
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(future.poll_count(), 1);
+}
+
+/// Test that rearming a completed future resets completion and drops the old future once.
+#[test]
+fn rearm_after_completion() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll},
+    };
+
+    struct Counted(Arc<AtomicUsize>, u32);
+
+    impl Future for Counted {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u32> {
+            Poll::Ready(self.1)
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut cx = Context::from_waker(std::task::Waker::noop());
+    let mut future = Box::pin(StackFuture::<_, 64>::new(Counted(drops.clone(), 1)).unwrap());
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(1));
+    assert!(future.is_completed());
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+    future.as_mut().rearm(Counted(drops.clone(), 2)).unwrap();
+    assert!(!future.is_completed());
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    let res = future
+        .as_mut()
+        .poll_replace(&mut cx, || Counted(drops.clone(), 3));
+    assert_eq!(res, Poll::Ready(2));
+    assert!(!future.is_completed());
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(3));
+    drop(future);
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+/// Test counting polls between resets, e.g. for fairness in a scheduler.
+#[test]
+fn polls_since_reset() {
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    let mut future =
+        std::pin::pin!(StackFuture::<_, 64>::new(std::future::pending::<()>()).unwrap());
+    for _ in 0..3 {
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+    }
+    assert_eq!(future.polls_since_reset(), 3);
+    future.as_mut().reset_poll_count();
+    assert_eq!(future.polls_since_reset(), 0);
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(future.polls_since_reset(), 1);
+    assert_eq!(future.poll_count(), 4);
+}
+
+/// Test moving a stack future into a larger and then a smaller buffer.
+#[tokio::test]
+async fn resize() {
+    let future = StackFuture::<_, 256>::new(complex()).unwrap();
+    let size = future.stored_size();
+    let future = future.resize::<1024>().map_err(|(e, _)| e).unwrap();
+    let (err, future) = future.resize::<16>().unwrap_err();
+    assert!(matches!(
+        err,
+        CreateError::SizeTooLarge { size: s, max_size: 16, .. } if s == size
+    ));
+    assert!(err.to_string().contains("complex"));
+    let future = future.resize::<256>().map_err(|(e, _)| e).unwrap();
+    assert_eq!(future.await, 4950);
+}
+
+/// Test splitting a stack future into raw parts and reassembling it.
+#[tokio::test]
+async fn raw_parts() {
+    let future = StackFuture::<_, 256>::new(complex()).unwrap();
+    let (buffer, vtable) = future.into_raw();
+    // The buffer can be stored without the alignment of the stack future.
+    let moved = Box::new((0u8, buffer));
+    let future = unsafe { StackFuture::<_, 256>::from_raw(moved.1, vtable) };
+    assert_eq!(future.await, 4950);
+}
+
+/// Test that only the raw parts of a Send stack future can move between threads.
+#[test]
+fn raw_parts_send() {
+    assert_not_impl_any!(RawVTable<u64>: Send, Sync);
+    assert_impl_all!(SendRawVTable<u64>: Send, Sync);
+
+    let future = LocalStackFuture::<_, 16>::new(async { Rc::new(1u64) }).unwrap();
+    let (buffer, vtable) = future.into_raw();
+    let vtable = vtable.into_send().unwrap_err();
+    drop(unsafe { LocalStackFuture::<_, 16>::from_raw(buffer, vtable) });
+
+    let future = StackFuture::<_, 16>::new(async { 42u64 }).unwrap();
+    let (buffer, vtable) = future.into_raw();
+    let vtable = vtable.into_send().unwrap();
+    let future = std::thread::spawn(move || unsafe {
+        StackFuture::<_, 16>::from_raw(buffer, vtable.into())
+    })
+    .join()
+    .unwrap();
+    assert_eq!(futures::executor::block_on(future), 42);
+}
+
+/// Test that the cancellation callback only runs if the future is dropped early.
+#[tokio::test]
+async fn new_with_cancel() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let cancelled = Arc::new(AtomicUsize::new(0));
+    let counter = cancelled.clone();
+    let future = StackFuture::<_, 256>::new_with_cancel(complex(), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+    assert_eq!(future.await, 4950);
+    assert_eq!(cancelled.load(Ordering::SeqCst), 0);
+
+    let counter = cancelled.clone();
+    let future = LocalStackFuture::<_, 256>::new_with_cancel(complex(), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+    let mut future = Box::pin(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    drop(future);
+    assert_eq!(cancelled.load(Ordering::SeqCst), 1);
+}
+
+/// Test that inspect sees the output once and passes it on.
+#[tokio::test]
+async fn inspect() {
+    let seen = std::sync::Mutex::new(Vec::new());
+    let future: StackFuture<_, 128> = StackFuture::<_, 32>::new(simple())
+        .unwrap()
+        .inspect(|x| seen.lock().unwrap().push(*x))
+        .unwrap();
+    assert_eq!(future.await, 42);
+    let future: LocalStackFuture<_, 128> = LocalStackFuture::<_, 32>::new(simple())
+        .unwrap()
+        .inspect(|x| seen.lock().unwrap().push(*x + 1))
+        .unwrap();
+    assert_eq!(future.await, 42);
+    assert_eq!(*seen.lock().unwrap(), [42, 43]);
+}
+
+/// Test reporting how much of the buffer is used.
+#[test]
+fn utilization() {
+    let future = StackFuture::<_, 64>::new(std::future::ready(1u128)).unwrap();
+    assert_eq!(future.utilization(), 0.5);
+    let future = LocalStackFuture::<_, 0>::new(std::future::pending::<()>()).unwrap();
+    assert_eq!(future.utilization(), 1.0);
+}
+
+/// Test describing the buffer layout, and serializing the description.
+#[test]
+fn info() {
+    use stack_future::{StackFutureInfo, Storage};
+
+    let future = StackFuture::<_, 64>::new(std::future::ready(1u128)).unwrap();
+    let info = future.info();
+    assert_eq!(
+        info,
+        StackFutureInfo {
+            capacity: 64,
+            stored_size: 32,
+            stored_align: 16,
+            storage: Storage::Inline,
+        }
+    );
+    #[cfg(feature = "serde")]
+    assert_eq!(
+        serde_json::to_string(&info).unwrap(),
+        r#"{"capacity":64,"stored_size":32,"stored_align":16,"storage":"Inline"}"#
+    );
+}
+
+/// Test erasing a poll function that completes after three polls.
+#[tokio::test]
+async fn poll_fn() {
+    let mut polls = 0;
+    let future = StackFuture::<_, 16>::poll_fn(move |cx| {
+        polls += 1;
+        if polls < 3 {
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        } else {
+            std::task::Poll::Ready(polls)
+        }
+    })
+    .unwrap();
+    assert_eq!(future.await, 3);
+    let future = LocalStackFuture::<_, 16>::poll_fn(|_| std::task::Poll::Ready(42)).unwrap();
+    assert_eq!(future.poll_count(), 0);
+    assert_eq!(future.await, 42);
+}
+
+/// Test computing the buffer size of an async fn without calling it.
+#[test]
+fn sized_for() {
+    let size = StackFuture::<_, 64>::sized_for(complex);
+    assert_eq!(size, std::mem::size_of_val(&complex()));
+    let size = LocalStackFuture::<_, 64>::sized_for(|| -> std::future::Ready<u64> {
+        panic!("probe must not be called")
+    });
+    assert_eq!(size, std::mem::size_of::<std::future::Ready<u64>>());
+}
+
+/// Test sequencing a second future after a stack future, in the same buffer.
+#[tokio::test]
+async fn then() {
+    let first = StackFuture::<_, 64>::new(async {
+        tokio::task::yield_now().await;
+        10
+    })
+    .unwrap();
+    let future: StackFuture<_, 512> = first.then(|n| async move { n + complex().await }).unwrap();
+    assert_eq!(future.await, 4960);
+
+    // The first future is dropped before the closure runs.
+    let value = Rc::new(());
+    let guard = value.clone();
+    let first = LocalStackFuture::<_, 16>::poll_fn(move |_| {
+        std::task::Poll::Ready(Rc::strong_count(&guard))
+    })
+    .unwrap();
+    let weak = Rc::downgrade(&value);
+    let future: LocalStackFuture<_, 256> = first
+        .then(move |count| async move { (count, weak.strong_count()) })
+        .unwrap();
+    assert_eq!(future.await, (2, 1));
+
+    let first = LocalStackFuture::<_, 64>::new(simple()).unwrap();
+    let res = first.then::<_, _, 16>(|_| simple());
+    assert!(matches!(res, Err(CreateError::SizeTooLarge { .. })));
+}
+
+/// Test filling an array with pending placeholders and rearming them.
+#[test]
+fn default_pending() {
+    let mut slots: Pin<Box<[StackFuture<u64, 16>; 4]>> = Box::pin(Default::default());
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    assert_eq!(stack_future::poll_all(slots.as_mut(), &mut cx), [None; 4]);
+    for i in 0..4 {
+        // Safe because the slots are structurally pinned in the array.
+        let slot = unsafe { slots.as_mut().map_unchecked_mut(|slots| &mut slots[i]) };
+        slot.rearm(std::future::ready(i as u64)).unwrap();
+    }
+    assert_eq!(
+        stack_future::poll_all(slots.as_mut(), &mut cx),
+        [Some(0), Some(1), Some(2), Some(3)]
+    );
+
+    let local = LocalStackFuture::<u64, 16>::default();
+    assert_eq!(local.stored_size(), 0);
+    assert_eq!(local.now_or_never(), None);
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "futures-sink")]
+use std::{pin::pin, rc::Rc};
+
+use futures::{SinkExt, StreamExt, channel::mpsc};
+use stack_future::{CreateError, LocalStackSink, StackSink};
+use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+/// Tests that the wrapped sinks work, and also that they fail if size is wrong.
+#[tokio::test]
+async fn smoke_test() {
+    let (tx, rx) = mpsc::channel::<u64>(4);
+    let mut sink = pin!(StackSink::<_, _, 64>::new(tx).unwrap());
+    for i in 0..3 {
+        sink.send(i).await.unwrap();
+    }
+    sink.close().await.unwrap();
+    assert_eq!(rx.collect::<Vec<_>>().await, vec![0, 1, 2]);
+
+    let (tx, rx) = mpsc::channel::<u64>(4);
+    let rc = Rc::new(2u64);
+    let sink = tx.with(move |x: u64| std::future::ready(Ok::<_, mpsc::SendError>(x * *rc)));
+    let mut sink = pin!(LocalStackSink::<_, _, 256>::new(sink).unwrap());
+    sink.send(21).await.unwrap();
+    sink.close().await.unwrap();
+    assert_eq!(rx.collect::<Vec<_>>().await, vec![42]);
+
+    let (tx, _rx) = mpsc::channel::<u64>(4);
+    let res = StackSink::<_, _, 4>::new(tx);
+    assert!(
+        matches!(res, Err(CreateError::SizeTooLarge { .. })),
+        "Expected error for too large sink"
+    );
+}
+
+assert_not_impl_any!(LocalStackSink<'static, u64, (), 128>: Send, Unpin);
+assert_impl_all!(StackSink<'static, u64, (), 128>: Send);
+assert_not_impl_any!(StackSink<'static, u64, (), 128>: Unpin);
@@ -0,0 +1,40 @@
+#![cfg(feature = "futures")]
+use std::{pin::pin, rc::Rc};
+
+use futures::{StreamExt, stream};
+use stack_future::{CreateError, LocalStackStream, StackStream};
+use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+/// Tests that the wrapped streams work, and also that they fail if size is wrong.
+#[tokio::test]
+async fn smoke_test() {
+    let mut s = pin!(StackStream::<_, 64>::new(stream::iter(0u64..10)).unwrap());
+    let mut sum = 0;
+    while let Some(x) = s.next().await {
+        sum += x;
+    }
+    assert_eq!(sum, 45);
+    let rc = Rc::new(2u64);
+    let s = LocalStackStream::<_, 64>::new(stream::iter(0u64..3).map(move |x| x * *rc)).unwrap();
+    assert_eq!(s.collect::<Vec<_>>().await, vec![0, 2, 4]);
+    let res = StackStream::<_, 4>::new(stream::iter(0u64..10));
+    assert!(
+        matches!(res, Err(CreateError::SizeTooLarge { .. })),
+        "Expected error for too large stream"
+    );
+}
+
+assert_not_impl_any!(LocalStackStream<'static, u64, 128>: Send, Unpin);
+assert_impl_all!(StackStream<'static, u64, 128>: Send);
+assert_not_impl_any!(StackStream<'static, u64, 128>: Unpin);
+
+/// Tests that items can be awaited one by one without `StreamExt`.
+#[tokio::test]
+async fn next_item() {
+    let mut s = pin!(StackStream::<_, 64>::new(stream::iter(0u64..2)).unwrap());
+    assert_eq!(s.as_mut().next_item().await, Some(0));
+    assert_eq!(s.as_mut().next_item().await, Some(1));
+    assert_eq!(s.as_mut().next_item().await, None);
+    let mut s = pin!(LocalStackStream::<_, 64>::new(stream::iter(0u64..1)).unwrap());
+    assert_eq!(s.as_mut().next_item().await, Some(0));
+}
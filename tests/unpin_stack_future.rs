@@ -0,0 +1,44 @@
+use std::future::{Future, ready};
+
+use stack_future::{LocalUnpinStackFuture, UnpinStackFuture};
+use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+assert_impl_all!(UnpinStackFuture<'static, u64, 128>: Send, Unpin);
+assert_impl_all!(LocalUnpinStackFuture<'static, u64, 128>: Unpin);
+assert_not_impl_any!(LocalUnpinStackFuture<'static, u64, 128>: Send);
+
+/// Tests that Unpin futures can be polled through a mutable reference without boxing.
+#[tokio::test]
+async fn smoke_test() {
+    fn assert_unpin<F: Future + Unpin>(f: F) -> F {
+        f
+    }
+    let mut future = assert_unpin(UnpinStackFuture::<_, 32>::new_unpin(ready(42u64)).unwrap());
+    assert_eq!((&mut future).await, 42);
+    let future = LocalUnpinStackFuture::<_, 32>::new_unpin(ready(42u64)).unwrap();
+    assert_eq!(future.capacity(), 32);
+    assert_eq!(future.await, 42);
+    let res = UnpinStackFuture::<_, 4>::new_unpin(ready(42u64));
+    assert!(res.is_err());
+}
+
+/// Tests polling through a mutable borrow repeatedly, without consuming the future.
+#[tokio::test]
+async fn poll_through_mut_ref() {
+    let mut polls = 0u64;
+    let mut future = UnpinStackFuture::<_, 32>::new_unpin(std::future::poll_fn(move |cx| {
+        polls += 1;
+        if polls < 3 {
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        } else {
+            std::task::Poll::Ready(polls)
+        }
+    }))
+    .unwrap();
+    for _ in 0..2 {
+        assert!(futures::poll!(&mut future).is_pending());
+    }
+    assert_eq!(future.poll_count(), 2);
+    assert_eq!((&mut future).await, 3);
+}